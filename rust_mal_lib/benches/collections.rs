@@ -0,0 +1,145 @@
+//! Compare the copy-on-update cost of the standard collections with the
+//! persistent ones now backing MAL lists, vectors and hash maps.
+//!
+//! Run with `cargo bench -p rust-mal-lib --bench collections`.
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use rust_mal_lib::core;
+use rust_mal_lib::types::{
+    new_hash, new_integer, new_keyword, new_list, new_vector, MalHashContainer, MalHashKey,
+    MalList, MalValue,
+};
+
+const SIZE: usize = 100_000;
+const UPDATES: usize = 1_000;
+
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+    println!(
+        "{:<40} {:>10.3} ms ({:.3} us/update)",
+        name,
+        elapsed.as_secs_f64() * 1e3,
+        elapsed.as_secs_f64() * 1e6 / UPDATES as f64
+    );
+}
+
+fn key(i: usize) -> MalHashKey {
+    MalHashKey::new(new_keyword(format!("k{}", i)))
+}
+
+fn main() {
+    let items: Vec<MalValue> = (0..SIZE).map(|i| new_integer(i as i32)).collect();
+    let std_map: BTreeMap<MalHashKey, MalValue> = items
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (key(i), v.clone()))
+        .collect();
+    let persistent_list = MalList::from(items.clone());
+    let persistent_map: MalHashContainer = std_map.clone().into_iter().collect();
+
+    println!(
+        "{} functional updates on {}-element collections, keeping every version:",
+        UPDATES, SIZE
+    );
+
+    bench("conj (std Vec, clone + push)", || {
+        let versions: Vec<Vec<MalValue>> = (0..UPDATES)
+            .map(|i| {
+                let mut seq = items.clone();
+                seq.push(new_integer(i as i32));
+                seq
+            })
+            .collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+    bench("conj (persistent vector)", || {
+        let versions: Vec<MalList> = (0..UPDATES)
+            .map(|i| {
+                let mut seq = persistent_list.clone();
+                seq.push_back(new_integer(i as i32));
+                seq
+            })
+            .collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+
+    bench("cons (std Vec, clone + insert(0))", || {
+        let versions: Vec<Vec<MalValue>> = (0..UPDATES)
+            .map(|i| {
+                let mut seq = items.clone();
+                seq.insert(0, new_integer(i as i32));
+                seq
+            })
+            .collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+    bench("cons (persistent vector)", || {
+        let versions: Vec<MalList> = (0..UPDATES)
+            .map(|i| {
+                let mut seq = persistent_list.clone();
+                seq.push_front(new_integer(i as i32));
+                seq
+            })
+            .collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+
+    bench("rest (std Vec, [1..].to_vec())", || {
+        let versions: Vec<Vec<MalValue>> = (0..UPDATES).map(|_| items[1..].to_vec()).collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+    bench("rest (persistent vector, skip(1))", || {
+        let versions: Vec<MalList> = (0..UPDATES).map(|_| persistent_list.skip(1)).collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+
+    bench("assoc (std BTreeMap, clone + insert)", || {
+        let versions: Vec<BTreeMap<MalHashKey, MalValue>> = (0..UPDATES)
+            .map(|i| {
+                let mut map = std_map.clone();
+                map.insert(key(i), new_integer(-1));
+                map
+            })
+            .collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+    bench("assoc (persistent ordered map)", || {
+        let versions: Vec<MalHashContainer> = (0..UPDATES)
+            .map(|i| {
+                let mut map = persistent_map.clone();
+                map.insert(key(i), new_integer(-1));
+                map
+            })
+            .collect();
+        assert_eq!(versions.len(), UPDATES);
+    });
+
+    println!("through the MAL core functions:");
+    let ns = core::ns();
+    let (conj, cons, assoc) = (&ns["conj"], &ns["cons"], &ns["assoc"]);
+    let vector = new_vector(persistent_list.clone());
+    let list = new_list(persistent_list.clone());
+    let hash = new_hash(persistent_map.clone());
+    bench("(conj vector i)", || {
+        for i in 0..UPDATES {
+            conj.apply(vec![vector.clone(), new_integer(i as i32)])
+                .unwrap();
+        }
+    });
+    bench("(cons i list)", || {
+        for i in 0..UPDATES {
+            cons.apply(vec![new_integer(i as i32), list.clone()])
+                .unwrap();
+        }
+    });
+    bench("(assoc hash-map k i)", || {
+        for i in 0..UPDATES {
+            assoc
+                .apply(vec![hash.clone(), key(i).value().clone(), new_integer(-1)])
+                .unwrap();
+        }
+    });
+}
@@ -0,0 +1,105 @@
+//! Compare the symbol lookups in a 20-deep environment chain: the previous
+//! find-then-get lookup, the single walk of 'get_env_value', the lexical
+//! address of 'env::lookup_at', and a function body evaluated with and
+//! without lexical addressing.
+//!
+//! Run with `cargo bench -p rust-mal-lib --bench env_lookup`.
+use std::time::Instant;
+
+use rust_mal_lib::env::{self, Env, Environment};
+use rust_mal_lib::symbol::SymbolId;
+use rust_mal_lib::types::{new_integer, new_symbol};
+use rust_mal_lib::{core, eval, reader};
+
+const DEPTH: usize = 20;
+const LOOKUPS: usize = 1_000_000;
+const CALLS: usize = 10_000;
+
+fn bench<F: FnMut()>(name: &str, count: usize, mut f: F) {
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+    println!(
+        "{:<45} {:>10.3} ms ({:.1} ns/op)",
+        name,
+        elapsed.as_secs_f64() * 1e3,
+        elapsed.as_secs_f64() * 1e9 / count as f64
+    );
+}
+
+/// Return the innermost environment of a DEPTH-deep chain, each one binding
+/// a few symbols, with the looked up one only bound in the root.
+fn nested_env() -> Env {
+    let mut env: Env = Environment::new(None);
+    env.set_env_value(new_symbol("x"), new_integer(42));
+    for depth in 0..DEPTH {
+        env = env.new_inner();
+        for i in 0..4 {
+            env.set_env_value(
+                new_symbol(&format!("local-{}-{}", depth, i)),
+                new_integer(i),
+            );
+        }
+    }
+    env
+}
+
+/// Return the source of a function whose body reads its parameter under
+/// DEPTH - 1 nested let*.
+fn nested_fn_source() -> String {
+    let mut source = String::from("(fn* (a) ");
+    for depth in 1..DEPTH {
+        source.push_str(&format!("(let* (b{} {}) ", depth, depth));
+    }
+    source.push_str("(+ a (+ a a))");
+    source.push_str(&")".repeat(DEPTH));
+    source
+}
+
+fn bench_function_calls(name: &str, lexical_addressing: bool) {
+    eval::set_lexical_addressing(lexical_addressing);
+    let mut repl_env: Env = Environment::new(None);
+    for (symbol, value) in core::ns() {
+        repl_env.set_env_value(new_symbol(&symbol), value);
+    }
+    let f = eval::eval(reader::read_str(&nested_fn_source()).unwrap(), repl_env).unwrap();
+    bench(name, CALLS, || {
+        for i in 0..CALLS {
+            f.apply(vec![new_integer(i as i32)]).unwrap();
+        }
+    });
+}
+
+fn main() {
+    let env = nested_env();
+    let key = new_symbol("x");
+    let symbol = SymbolId::intern("x");
+
+    println!(
+        "{} lookups of a symbol bound {} environments up:",
+        LOOKUPS, DEPTH
+    );
+    bench("find then get (previous lookup)", LOOKUPS, || {
+        for _ in 0..LOOKUPS {
+            env::find(&env, &key).unwrap().get_env_value(&key).unwrap();
+        }
+    });
+    bench("get_env_value (single walk)", LOOKUPS, || {
+        for _ in 0..LOOKUPS {
+            env.get_env_value(&key).unwrap();
+        }
+    });
+    bench("lookup_at (lexical address)", LOOKUPS, || {
+        for _ in 0..LOOKUPS {
+            env::lookup_at(&env, symbol, DEPTH).unwrap();
+        }
+    });
+
+    println!(
+        "{} calls of a function reading its parameter under {} let*:",
+        CALLS,
+        DEPTH - 1
+    );
+    bench_function_calls("without lexical addressing", false);
+    bench_function_calls("with lexical addressing", true);
+}
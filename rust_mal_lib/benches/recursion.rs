@@ -0,0 +1,59 @@
+//! Measure the evaluation of recursion-heavy MAL programs, dominated by the
+//! symbol lookups and the special forms dispatch (both on interned symbols).
+//!
+//! Run with `cargo bench -p rust-mal-lib --bench recursion`.
+use std::time::Instant;
+
+use rust_mal_lib::env::{Env, Environment};
+use rust_mal_lib::types::new_symbol;
+use rust_mal_lib::{core, eval, reader};
+
+const RUNS: usize = 5;
+
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<40} {:>10.3} ms/run",
+        name,
+        elapsed.as_secs_f64() * 1e3 / RUNS as f64
+    );
+}
+
+fn rep(input: &str, env: &Env) -> String {
+    let ast = reader::read_str(input).unwrap();
+    eval::eval(ast, env.clone()).unwrap().pr_str(true)
+}
+
+fn main() {
+    let env: Env = Environment::new(None);
+    for (symbol, value) in core::ns() {
+        env.clone().set_env_value(new_symbol(&symbol), value);
+    }
+    rep(
+        "(def! fib (fn* (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))",
+        &env,
+    );
+    rep(
+        concat!(
+            "(def! count-down (fn* (n acc) ",
+            "(let* (m (- n 1)) (if (= n 0) acc (count-down m (+ acc 1))))))"
+        ),
+        &env,
+    );
+    rep(
+        "(def! ackermann (fn* (m n) (if (= m 0) (+ n 1) (if (= n 0) (ackermann (- m 1) 1) (ackermann (- m 1) (ackermann m (- n 1)))))))",
+        &env,
+    );
+
+    bench("(fib 22)", || assert_eq!(rep("(fib 22)", &env), "17711"));
+    bench("(count-down 100000 0) (tail calls)", || {
+        assert_eq!(rep("(count-down 100000 0)", &env), "100000")
+    });
+    bench("(ackermann 2 200)", || {
+        assert_eq!(rep("(ackermann 2 200)", &env), "403")
+    });
+}
@@ -0,0 +1,51 @@
+//! Compare the tree-walking evaluator with the bytecode virtual machine and
+//! the evaluation of the analyzed forms.
+//!
+//! Run with `cargo bench -p rust-mal-lib --bench vm`.
+use std::time::Instant;
+
+use rust_mal_lib::env::Env;
+use rust_mal_lib::types::{MalResult, MalValue};
+use rust_mal_lib::{analyzer, eval, namespace, reader, vm};
+
+fn bench(name: &str, backend: fn(MalValue, Env) -> MalResult, input: &str, expected: &str) {
+    let env = namespace::new_interpreter();
+    let rep = |input: &str| {
+        backend(reader::read_str(input).unwrap(), env.clone())
+            .unwrap()
+            .pr_str(true)
+    };
+    rep("(def! fib (fn* (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))");
+    rep("(def! count-up (fn* (n acc) (if (= n 0) acc (count-up (- n 1) (+ acc 1)))))");
+    let start = Instant::now();
+    assert_eq!(rep(input), expected);
+    println!(
+        "{:<30} {:>10.3} ms",
+        name,
+        start.elapsed().as_secs_f64() * 1e3
+    );
+}
+
+fn main() {
+    bench("(fib 25) tree walker", eval::eval, "(fib 25)", "75025");
+    bench("(fib 25) virtual machine", vm::eval, "(fib 25)", "75025");
+    bench("(fib 25) analyzer", analyzer::eval, "(fib 25)", "75025");
+    bench(
+        "loop of 1M tree walker",
+        eval::eval,
+        "(count-up 1000000 0)",
+        "1000000",
+    );
+    bench(
+        "loop of 1M virtual machine",
+        vm::eval,
+        "(count-up 1000000 0)",
+        "1000000",
+    );
+    bench(
+        "loop of 1M analyzer",
+        analyzer::eval,
+        "(count-up 1000000 0)",
+        "1000000",
+    );
+}
@@ -0,0 +1,730 @@
+/// Module analyzing MAL forms into a tree of typed nodes, evaluated by an
+/// alternative backend to the tree-walking 'eval', with the same semantics.
+///
+/// A form is analyzed once before being evaluated, and a function body once
+/// when the function is defined (not at each call): the special forms are
+/// recognized and checked then, so that a malformed one in a function body
+/// fails at its definition. The local bindings (the functions parameters and
+/// the let* bindings) are resolved to slots of the environments, at a
+/// lexical address, and are not looked up by name: a binding pattern (see
+/// 'destructure') binds each of its symbols to a slot.
+///
+/// The functions created by the analyzed forms bind their parameters to
+/// slots: the other backends can still call them (as any function, see
+/// 'MalFunctionData::binds_by_name'), and they can call the functions of the
+/// other backends. The let* and fn* forms whose body uses def! or undef!
+/// (which change the bindings by name of the environment they are evaluated
+/// in), as well as undef! and the namespaces special forms, are delegated to
+/// 'eval'.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::destructure::{self, Pattern};
+use super::env::{self, Env};
+use super::eval;
+use super::limits;
+use super::namespace;
+use super::symbol::{self, SymbolId};
+use super::types;
+use super::types::MalType::{self, *};
+use super::types::{
+    name_function, new_false, new_hash, new_lazy_seq, new_mal_function_binding, new_nil, new_set,
+    new_true, new_vector, Binding, MalError, MalErrorKind, MalHashContainer, MalHashKey, MalList,
+    MalResult, MalSetContainer, MalValue, Source,
+};
+
+/// How a value is bound to the slots of the environment being filled.
+pub enum Binder {
+    /// Bound to the given slot, naming the functions after the given symbol
+    /// (of a let* binding) if any.
+    Slot(usize, Option<SymbolId>),
+    /// Destructured with a binding pattern.
+    Pattern(Box<PatternBinder>),
+}
+
+/// A binding pattern, with the slots of its symbols.
+pub struct PatternBinder {
+    pattern: Pattern,
+    slots: Vec<(SymbolId, usize)>,
+    /// The analyzed default values of the pattern, by address of their form.
+    defaults: Vec<(*const MalType, Node)>,
+}
+
+/// Analyzed values, each with how it is bound.
+pub type Bindings = Vec<(Binder, Node)>;
+
+/// An analyzed form.
+pub enum Node {
+    /// A self-evaluating value.
+    Const(MalValue),
+    /// A local binding at the given lexical address: in the given slot of
+    /// the given number of environments up from the evaluating one.
+    LocalRef(usize, usize),
+    /// A let* binding which may not be bound yet when evaluated (referred to
+    /// by a binding preceding it), at the given lexical address, otherwise
+    /// the given outer binding.
+    LetRef(usize, usize, Box<Node>),
+    /// A binding of the namespace (or qualified by one), looked up by name.
+    GlobalRef(SymbolId),
+    If(Box<Node>, Box<Node>, Box<Node>),
+    Do(Vec<Node>),
+    /// Evaluate the values in a new environment with the given number of
+    /// slots, binding each one to its slots in order, then the body in it.
+    Let(usize, Bindings, Box<Node>),
+    /// Evaluate as 'Let', then the body again each time it evaluates a
+    /// 'Recur'.
+    Loop(usize, Bindings, Box<Node>),
+    /// Rebind the slots of the innermost loop to the values, in a new
+    /// environment, and evaluate its body again.
+    Recur(Vec<Node>),
+    Fn(Rc<FnNode>),
+    /// Bind the value to the given symbol, with the given docstring if any.
+    Def(SymbolId, Option<String>, Box<Node>),
+    /// The value of the given reference, nil if it is unbound.
+    Resolve(Box<Node>),
+    /// Whether the given reference is bound.
+    Bound(Box<Node>),
+    LazySeq(Rc<Node>),
+    Call(Box<Node>, Vec<Node>),
+    Vector(Vec<Node>),
+    /// A hash map of the given (unevaluated) keys to their values.
+    Hash(Vec<(MalValue, Node)>),
+    Set(Vec<Node>),
+    /// A form delegated to 'eval'.
+    Eval(MalValue),
+}
+
+/// The definition of a function, as analyzed from a fn* form (the body of
+/// each of its arities being in 'ANALYZED').
+pub struct FnNode {
+    clauses: Vec<(MalValue, MalValue)>,
+    source: Option<Rc<Source>>,
+}
+
+/// The symbols bound to the slots of an environment being analyzed.
+struct Scope {
+    symbols: Vec<SymbolId>,
+    /// The number of slots already bound (all of them, except while the
+    /// let* bindings are analyzed).
+    bound: usize,
+    /// Whether this is the scope of a loop, whose bindings recur rebinds.
+    is_loop: bool,
+}
+
+/// The scopes of the environments being analyzed, from the outermost one.
+type Scopes = Vec<Scope>;
+
+fn analysis_error<T>(kind: MalErrorKind, message: &str) -> Result<T, MalError> {
+    Err(MalError::new(kind, message))
+}
+
+/// Return true if the given AST contains a def!, defn or undef! form.
+fn rebinds(ast: &MalValue) -> bool {
+    match **ast {
+        List(ref seq, _) => {
+            matches!(
+                seq.front().map(|head| &**head),
+                Some(&Symbol(symbol::DEF))
+                    | Some(&Symbol(symbol::DEFN))
+                    | Some(&Symbol(symbol::UNDEF))
+            ) || seq.iter().any(rebinds)
+        }
+        Vector(ref seq) => seq.iter().any(rebinds),
+        Hash(ref hash) => hash.values().any(rebinds),
+        Set(ref set) => set.iter().any(|item| rebinds(item.value())),
+        _ => false,
+    }
+}
+
+/// Analyze a reference to the given symbol, bound in one of the given
+/// number of outermost scopes or by name.
+fn analyze_symbol(symbol: SymbolId, scopes: &[Scope], outermost: usize) -> Node {
+    for (index, scope) in scopes[..outermost].iter().enumerate().rev() {
+        if let Some(slot) = scope.symbols.iter().position(|&bound| bound == symbol) {
+            let depth = scopes.len() - 1 - index;
+            return if slot < scope.bound {
+                Node::LocalRef(depth, slot)
+            } else {
+                Node::LetRef(depth, slot, Box::new(analyze_symbol(symbol, scopes, index)))
+            };
+        }
+    }
+    Node::GlobalRef(symbol)
+}
+
+/// Return the symbols bound to the slots of the given parameters list,
+/// checked as 'env::bind' does: the required ones then, if any, the variadic
+/// one. The parameters which are binding patterns are bound to hidden
+/// symbols, returned with their pattern.
+fn analyze_params(params: &MalValue) -> Result<(Vec<SymbolId>, MalList), MalError> {
+    let forms = match **params {
+        List(ref forms, _) | Vector(ref forms) => forms,
+        _ => return analysis_error(MalErrorKind::Syntax, "fn* with non-list arguments"),
+    };
+    destructure::parse_params(params)?;
+    let mut symbols = vec![];
+    let mut patterns = MalList::new();
+    for param in forms {
+        match **param {
+            Symbol(symbol::AMPERSAND) => (),
+            Symbol(param) => symbols.push(param),
+            _ => {
+                let hidden = SymbolId::intern(&format!(" destructured {}", patterns.len() / 2));
+                symbols.push(hidden);
+                patterns.push_back(param.clone());
+                patterns.push_back(Rc::new(Symbol(hidden)));
+            }
+        }
+    }
+    Ok((symbols, patterns))
+}
+
+/// Analyze the given form, evaluated in environments with the given scopes.
+fn analyze(ast: &MalValue, scopes: &mut Scopes) -> Result<Node, MalError> {
+    Ok(match **ast {
+        Symbol(symbol) | LocalSymbol(symbol, _) => analyze_symbol(symbol, scopes, scopes.len()),
+        List(ref seq, _) if !seq.is_empty() => return analyze_list(ast, seq, scopes),
+        Vector(ref seq) => Node::Vector(analyze_all(seq.iter(), scopes)?),
+        Hash(ref hash) => {
+            let mut items = vec![];
+            for (key, value) in hash {
+                items.push((key.value().clone(), analyze(value, scopes)?));
+            }
+            Node::Hash(items)
+        }
+        Set(ref set) => Node::Set(analyze_all(set.iter().map(MalHashKey::value), scopes)?),
+        _ => Node::Const(ast.clone()),
+    })
+}
+
+fn analyze_all<'a, I>(forms: I, scopes: &mut Scopes) -> Result<Vec<Node>, MalError>
+where
+    I: Iterator<Item = &'a MalValue>,
+{
+    forms.map(|form| analyze(form, scopes)).collect()
+}
+
+fn analyze_list(ast: &MalValue, seq: &MalList, scopes: &mut Scopes) -> Result<Node, MalError> {
+    let head = match *seq[0] {
+        Symbol(symbol) => Some(symbol),
+        _ => None,
+    };
+    Ok(match head {
+        // (do items...)
+        Some(symbol::DO) => Node::Do(analyze_all(seq.iter().skip(1), scopes)?),
+        // (if condition then otherwise?)
+        Some(symbol::IF) => {
+            if seq.len() < 3 || seq.len() > 4 {
+                return analysis_error(MalErrorKind::Arity, "wrong arity for if, should be 3 or 4");
+            }
+            let otherwise = match seq.get(3) {
+                Some(otherwise) => analyze(otherwise, scopes)?,
+                None => Node::Const(new_nil()),
+            };
+            Node::If(
+                Box::new(analyze(&seq[1], scopes)?),
+                Box::new(analyze(&seq[2], scopes)?),
+                Box::new(otherwise),
+            )
+        }
+        // (def! key docstring? value)
+        Some(symbol::DEF) => {
+            let doc = match seq.len() {
+                3 => None,
+                4 => match *seq[2] {
+                    Str(ref doc) => Some(doc.clone()),
+                    _ => {
+                        return analysis_error(
+                            MalErrorKind::Syntax,
+                            "def! with non-string docstring",
+                        )
+                    }
+                },
+                _ => {
+                    return analysis_error(
+                        MalErrorKind::Arity,
+                        "wrong arity for def!, should be 2 or 3",
+                    )
+                }
+            };
+            match *seq[1] {
+                Symbol(key) => Node::Def(key, doc, Box::new(analyze(seq.last().unwrap(), scopes)?)),
+                _ => return analysis_error(MalErrorKind::Syntax, "def! with non-symbol as a key"),
+            }
+        }
+        // (let* (key0 value0 key1 value1 ...) body)
+        Some(symbol::LET) => {
+            if seq.len() != 3 {
+                return analysis_error(
+                    MalErrorKind::Arity,
+                    "wrong arity for \"let*\", should be 2",
+                );
+            }
+            let bindings = match *seq[1] {
+                List(ref bindings, _) | Vector(ref bindings) => bindings,
+                _ => return analysis_error(MalErrorKind::Syntax, "let* with non-list binding"),
+            };
+            if bindings.len() % 2 != 0 {
+                return analysis_error(
+                    MalErrorKind::Syntax,
+                    "missing key or value in the let* binding list",
+                );
+            }
+            let keys = bindings
+                .iter()
+                .step_by(2)
+                .map(destructure::parse)
+                .collect::<Result<Vec<_>, _>>()?;
+            if rebinds(ast) {
+                return Ok(Node::Eval(ast.clone()));
+            }
+            let (slots, values, body) = analyze_let(keys, bindings, &seq[2], false, scopes)?;
+            Node::Let(slots, values, Box::new(body))
+        }
+        // (loop (key0 value0 key1 value1 ...) body)
+        Some(symbol::LOOP) => {
+            let keys = eval::check_loop(seq)?;
+            if rebinds(ast) {
+                return Ok(Node::Eval(ast.clone()));
+            }
+            let bindings = match *seq[1] {
+                List(ref bindings, _) | Vector(ref bindings) => bindings,
+                _ => unreachable!(),
+            };
+            let (slots, values, body) = analyze_let(keys, bindings, &seq[2], true, scopes)?;
+            Node::Loop(slots, values, Box::new(body))
+        }
+        // (recur values...), checked with its loop
+        Some(symbol::RECUR) => {
+            if !scopes.iter().any(|scope| scope.is_loop) {
+                return analysis_error(
+                    MalErrorKind::Syntax,
+                    "recur must be in tail position of a loop",
+                );
+            }
+            Node::Recur(analyze_all(seq.iter().skip(1), scopes)?)
+        }
+        // (fn* (params...) body) or (fn* ((params...) body)...), the
+        // parameters which are binding patterns being destructured as by a
+        // let* around the body
+        Some(symbol::FN) => {
+            let clauses = eval::check_fn(seq)?;
+            let mut analyzed = vec![];
+            for (params, body) in &clauses {
+                analyzed.push((analyze_params(params)?, body));
+            }
+            if rebinds(ast) {
+                return Ok(Node::Eval(ast.clone()));
+            }
+            for ((symbols, patterns), body) in analyzed {
+                let bound = symbols.len();
+                scopes.push(Scope {
+                    symbols,
+                    bound,
+                    is_loop: false,
+                });
+                let node = if patterns.is_empty() {
+                    analyze(body, scopes)?
+                } else {
+                    let keys = patterns
+                        .iter()
+                        .step_by(2)
+                        .map(destructure::parse)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let (slots, values, node) = analyze_let(keys, &patterns, body, false, scopes)?;
+                    Node::Let(slots, values, Box::new(node))
+                };
+                scopes.pop();
+                define_analyzed(body, Rc::new(node));
+            }
+            Node::Fn(Rc::new(FnNode {
+                clauses,
+                source: types::source_of(ast),
+            }))
+        }
+        // (defn name docstring? params body), as the def! of a fn*
+        Some(symbol::DEFN) => return analyze(&eval::expand_defn(ast, seq)?, scopes),
+        // (resolve key) and (bound? key), on an unevaluated symbol
+        Some(symbol::RESOLVE) | Some(symbol::BOUND) => {
+            let name = head.unwrap().name();
+            if seq.len() != 2 {
+                return Err(MalError::new(
+                    MalErrorKind::Arity,
+                    format!("wrong arity for {}, should be 1", name),
+                ));
+            }
+            let reference = match *seq[1] {
+                Symbol(key) | LocalSymbol(key, _) => {
+                    Box::new(analyze_symbol(key, scopes, scopes.len()))
+                }
+                _ => {
+                    return Err(MalError::new(
+                        MalErrorKind::Syntax,
+                        format!("{} with non-symbol as a key", name),
+                    ))
+                }
+            };
+            if head == Some(symbol::RESOLVE) {
+                Node::Resolve(reference)
+            } else {
+                Node::Bound(reference)
+            }
+        }
+        // (lazy-seq body...)
+        Some(symbol::LAZY_SEQ) => {
+            Node::LazySeq(Rc::new(Node::Do(analyze_all(seq.iter().skip(1), scopes)?)))
+        }
+        Some(symbol::UNDEF)
+        | Some(symbol::NS_PUBLICS)
+        | Some(symbol::NS)
+        | Some(symbol::REQUIRE) => Node::Eval(ast.clone()),
+        // otherwise : a call of the first item with the other ones
+        _ => Node::Call(
+            Box::new(analyze(&seq[0], scopes)?),
+            analyze_all(seq.iter().skip(1), scopes)?,
+        ),
+    })
+}
+
+/// Analyze the given (checked) let* or loop bindings, with their parsed
+/// keys, and body, and return the number of slots of their environment, the
+/// values with their binders and the body.
+fn analyze_let(
+    keys: Vec<Pattern>,
+    bindings: &MalList,
+    body: &MalValue,
+    is_loop: bool,
+    scopes: &mut Scopes,
+) -> Result<(usize, Bindings, Node), MalError> {
+    // the slot of each symbol, the same for a symbol bound again
+    let mut symbols = vec![];
+    let mut slot_of = |symbol| match symbols.iter().position(|&bound| bound == symbol) {
+        Some(slot) => slot,
+        None => {
+            symbols.push(symbol);
+            symbols.len() - 1
+        }
+    };
+    let keys = keys
+        .into_iter()
+        .map(|key| match key {
+            Pattern::Symbol(symbol) => (key, vec![(symbol, slot_of(symbol))]),
+            pattern => {
+                let slots = pattern
+                    .symbols()
+                    .into_iter()
+                    .map(|symbol| (symbol, slot_of(symbol)))
+                    .collect();
+                (pattern, slots)
+            }
+        })
+        .collect::<Vec<_>>();
+    scopes.push(Scope {
+        symbols,
+        bound: 0,
+        is_loop,
+    });
+    let mut values = vec![];
+    for ((key, slots), value) in keys.into_iter().zip(bindings.iter().skip(1).step_by(2)) {
+        let value = analyze(value, scopes)?;
+        let binder = match key {
+            Pattern::Symbol(symbol) => Binder::Slot(slots[0].1, (!is_loop).then_some(symbol)),
+            pattern => {
+                // the default values are evaluated while the pattern is
+                // being bound
+                let mut defaults = vec![];
+                for form in pattern.defaults() {
+                    defaults.push((Rc::as_ptr(&form), analyze(&form, scopes)?));
+                }
+                Binder::Pattern(Box::new(PatternBinder {
+                    pattern,
+                    slots: slots.clone(),
+                    defaults,
+                }))
+            }
+        };
+        let scope = scopes.last_mut().unwrap();
+        for (_, slot) in slots {
+            scope.bound = scope.bound.max(slot + 1);
+        }
+        values.push((binder, value));
+    }
+    let body = analyze(body, scopes)?;
+    Ok((scopes.pop().unwrap().symbols.len(), values, body))
+}
+
+/// Analyze the given form, evaluated at the top level (i.e. outside of any
+/// function body or let*).
+pub fn analyze_form(ast: &MalValue) -> Result<Node, MalError> {
+    analyze(ast, &mut vec![])
+}
+
+/// Analyzed functions bodies, by address (see 'ANALYZED').
+type AnalyzedBodies = HashMap<*const MalType, (Weak<MalType>, Rc<Node>)>;
+
+thread_local! {
+    /// The analyzed functions bodies still alive, by address.
+    ///
+    /// The bodies are weakly held, which is enough to prevent their address
+    /// from being reused.
+    static ANALYZED: RefCell<AnalyzedBodies> = RefCell::new(HashMap::new());
+    /// The number of analyzed bodies above which the dead ones are dropped.
+    static ANALYZED_LIMIT: Cell<usize> = const { Cell::new(64) };
+}
+
+fn define_analyzed(body: &MalValue, node: Rc<Node>) {
+    ANALYZED.with(|analyzed| {
+        let mut analyzed = analyzed.borrow_mut();
+        if analyzed.len() >= ANALYZED_LIMIT.with(Cell::get) {
+            analyzed.retain(|_, (body, _)| body.strong_count() > 0);
+            ANALYZED_LIMIT.with(|limit| limit.set((2 * analyzed.len()).max(64)));
+        }
+        analyzed.insert(Rc::as_ptr(body), (Rc::downgrade(body), node));
+    });
+}
+
+/// Return the analyzed body of a function created by the analyzer.
+fn analyzed(body: &MalValue) -> Result<Rc<Node>, MalError> {
+    ANALYZED
+        .with(|analyzed| {
+            analyzed
+                .borrow()
+                .get(&Rc::as_ptr(body))
+                .map(|analyzed| analyzed.1.clone())
+        })
+        .ok_or_else(|| {
+            MalError::new(
+                MalErrorKind::Value,
+                format!("analyzer: function body {} not analyzed", body.pr_str(true)),
+            )
+        })
+}
+
+/// Evaluate the given AST in the given environment, by analyzing it then
+/// evaluating its nodes.
+pub fn eval(ast: MalValue, env: Env) -> MalResult {
+    run(Rc::new(analyze_form(&ast)?), env, None)
+}
+
+/// Evaluate the given body of a function created by the analyzer, in the
+/// environment binding its parameters.
+fn eval_body(body: MalValue, env: Env) -> MalResult {
+    run(analyzed(&body)?, env, None)
+}
+
+/// The evaluation of a node in tail position: either its value or the call
+/// of a function created by the analyzer, whose body is left to evaluate.
+enum Tail {
+    Value(MalValue),
+    /// The analyzed body, the environment binding the parameters and the name
+    /// of the function.
+    Call(Rc<Node>, Env, String),
+    /// The values of a recur.
+    Recur(Vec<MalValue>),
+}
+
+fn misplaced_recur() -> MalError {
+    MalError::new(
+        MalErrorKind::Syntax,
+        "recur must be in tail position of a loop",
+    )
+}
+
+/// Evaluate the given node in the given environment, the calls in tail
+/// position being evaluated in a loop rather than recursively (tail call
+/// optimization).
+///
+/// An error records the given name of the function being evaluated (if any)
+/// or, as 'eval' does, the one of the last function called in tail position.
+fn run(mut node: Rc<Node>, mut env: Env, mut frame: Option<String>) -> MalResult {
+    let _depth = limits::enter()?;
+    let result = loop {
+        match eval_tail(&node, &env) {
+            Ok(Tail::Value(value)) => break Ok(value),
+            Ok(Tail::Call(body, body_env, name)) => {
+                node = body;
+                env = body_env;
+                frame = Some(name);
+            }
+            Ok(Tail::Recur(_)) => break Err(misplaced_recur()),
+            Err(why) => break Err(why),
+        }
+    };
+    result.map_err(|why| match frame {
+        Some(name) => why.with_frame(name),
+        None => why,
+    })
+}
+
+fn unbound(symbol: SymbolId) -> MalError {
+    MalError::new(
+        MalErrorKind::UnboundSymbol,
+        format!("env: '{}' not found", symbol),
+    )
+}
+
+/// Bind the given value to the slots of the given environment being filled.
+fn bind(binder: &Binder, env: &Env, value: MalValue) -> Result<(), MalError> {
+    match *binder {
+        Binder::Slot(slot, name) => {
+            let value = match name {
+                Some(name) => name_function(value, &name.name(), None),
+                None => value,
+            };
+            env::set_slot(env, slot, value);
+            Ok(())
+        }
+        Binder::Pattern(ref binder) => binder.pattern.bind(
+            value,
+            &mut |symbol, value| {
+                let &(_, slot) = binder.slots.iter().find(|bound| bound.0 == symbol).unwrap();
+                env::set_slot(env, slot, value)
+            },
+            &mut |form| {
+                let (_, default) = binder
+                    .defaults
+                    .iter()
+                    .find(|default| default.0 == Rc::as_ptr(form))
+                    .unwrap();
+                eval_node(default, env)
+            },
+        ),
+    }
+}
+
+/// Evaluate the given node, in tail position, in the given environment.
+fn eval_tail(node: &Node, env: &Env) -> Result<Tail, MalError> {
+    limits::step()?;
+    match *node {
+        Node::If(ref condition, ref then, ref otherwise) => match *eval_node(condition, env)? {
+            False | Nil => eval_tail(otherwise, env),
+            _ => eval_tail(then, env),
+        },
+        Node::Do(ref items) => match items.split_last() {
+            Some((last, items)) => {
+                for item in items {
+                    eval_node(item, env)?;
+                }
+                eval_tail(last, env)
+            }
+            None => Ok(Tail::Value(new_nil())),
+        },
+        Node::Let(slots, ref values, ref body) => {
+            let env_let = env::new_frame(env, vec![None; slots]);
+            for (binder, value) in values {
+                bind(binder, &env_let, eval_node(value, &env_let)?)?;
+            }
+            eval_tail(body, &env_let)
+        }
+        Node::Loop(slots, ref values, ref body) => {
+            let mut env_loop = env::new_frame(env, vec![None; slots]);
+            for (binder, value) in values {
+                bind(binder, &env_loop, eval_node(value, &env_loop)?)?;
+            }
+            loop {
+                match eval_tail(body, &env_loop)? {
+                    Tail::Recur(evaluated) => {
+                        env_loop = env::new_frame(env, vec![None; slots]);
+                        for ((binder, _), value) in values.iter().zip(evaluated) {
+                            bind(binder, &env_loop, value)?;
+                        }
+                    }
+                    tail => return Ok(tail),
+                }
+            }
+        }
+        Node::Recur(ref values) => Ok(Tail::Recur(
+            values
+                .iter()
+                .map(|value| eval_node(value, env))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Node::Call(ref f, ref args) => {
+            let f = eval_node(f, env)?;
+            let args = args
+                .iter()
+                .map(|arg| eval_node(arg, env))
+                .collect::<Result<Vec<_>, _>>()?;
+            match *f {
+                MalFunction(ref data) if !data.binds_by_name() => {
+                    let (body, body_env) = data.bind(args)?;
+                    Ok(Tail::Call(
+                        analyzed(&body)?,
+                        body_env,
+                        data.frame_name().to_string(),
+                    ))
+                }
+                _ => f.apply(args).map(Tail::Value),
+            }
+        }
+        _ => eval_node(node, env).map(Tail::Value),
+    }
+}
+
+/// Evaluate the given node in the given environment.
+fn eval_node(node: &Node, env: &Env) -> MalResult {
+    match *node {
+        Node::Const(ref value) => Ok(value.clone()),
+        Node::LocalRef(depth, slot) => Ok(env::slot(env, depth, slot).unwrap()),
+        Node::LetRef(depth, slot, ref otherwise) => match env::slot(env, depth, slot) {
+            Some(value) => Ok(value),
+            None => eval_node(otherwise, env),
+        },
+        Node::GlobalRef(symbol) => env::lookup(env, symbol)
+            .or_else(|| namespace::lookup_qualified(env, symbol))
+            .ok_or_else(|| unbound(symbol)),
+        Node::Fn(ref proto) => Ok(new_mal_function_binding(
+            self::eval_body,
+            env.clone(),
+            proto.clauses.clone(),
+            proto.source.clone(),
+            Binding::Slots,
+        )),
+        Node::Def(symbol, ref doc, ref value) => {
+            let value = name_function(eval_node(value, env)?, &symbol.name(), doc.clone());
+            env::define(env, symbol, value.clone());
+            Ok(value)
+        }
+        Node::Resolve(ref reference) => Ok(eval_node(reference, env).unwrap_or_else(|_| new_nil())),
+        Node::Bound(ref reference) => Ok(match eval_node(reference, env) {
+            Ok(_) => new_true(),
+            Err(_) => new_false(),
+        }),
+        Node::LazySeq(ref body) => {
+            let (body, env) = (body.clone(), env.clone());
+            Ok(new_lazy_seq(Box::new(move || run(body, env, None))))
+        }
+        Node::Vector(ref items) => Ok(new_vector(
+            items
+                .iter()
+                .map(|item| eval_node(item, env))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Node::Hash(ref items) => {
+            let mut hash = MalHashContainer::new();
+            for (key, value) in items {
+                hash.insert(MalHashKey::new(key.clone()), eval_node(value, env)?);
+            }
+            Ok(new_hash(hash))
+        }
+        Node::Set(ref items) => {
+            let mut set = MalSetContainer::new();
+            for item in items {
+                set.insert(MalHashKey::new(eval_node(item, env)?));
+            }
+            Ok(new_set(set))
+        }
+        Node::Eval(ref form) => eval::eval(form.clone(), env.clone()),
+        Node::If(..)
+        | Node::Do(_)
+        | Node::Let(..)
+        | Node::Loop(..)
+        | Node::Recur(_)
+        | Node::Call(..) => match eval_tail(node, env)? {
+            Tail::Value(value) => Ok(value),
+            Tail::Call(body, body_env, name) => run(body, body_env, Some(name)),
+            Tail::Recur(_) => Err(misplaced_recur()),
+        },
+    }
+}
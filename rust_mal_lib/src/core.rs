@@ -1,10 +1,19 @@
 /// Module defining the core Rust functions made available in MAL.
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 
+use super::edn;
+use super::gc;
+use super::limits;
+use super::seq;
 use super::types;
 use super::types::MalType::*;
 use super::types::{
-    err_str, err_string, new_false, new_function, new_integer, new_true, MalResult, MalValue,
+    err_str, err_string, new_false, new_function, new_integer, new_nil, new_str, new_true,
+    MalError, MalErrorKind, MalFunctionData, MalHashKey, MalResult, MalSetContainer, MalValue,
 };
 
 fn eq_q(args: Vec<MalValue>) -> MalResult {
@@ -19,37 +28,1564 @@ fn eq_q(args: Vec<MalValue>) -> MalResult {
 
 /// Create and return the list (args...).
 fn list(args: Vec<MalValue>) -> MalResult {
+    limits::check_size(args.len())?;
     Ok(types::new_list(args))
 }
 /// Return true if the parameter is a list, false otherwise.
 fn list_q(args: Vec<MalValue>) -> MalResult {
     match *args[0] {
-        List(_) => Ok(new_true()),
+        List(..) => Ok(new_true()),
         _ => Ok(new_false()),
     }
 }
 
 // Sequence operations
 
-/// Return true if the parameter (must be a List/Vector) is empty, false otherwise.
+/// Return the number of items in the given collection, if it is one.
+fn collection_len(value: &MalValue) -> Option<usize> {
+    match **value {
+        List(ref seq, _) | Vector(ref seq) => Some(seq.len()),
+        Hash(ref hash) => Some(hash.len()),
+        Set(ref set) => Some(set.len()),
+        _ => None,
+    }
+}
+
+/// Return true if the parameter (must be seqable) is empty, false otherwise.
 fn empty_q(args: Vec<MalValue>) -> MalResult {
+    let empty = match collection_len(&args[0]) {
+        Some(len) => len == 0,
+        None => seq::first_rest(&args[0])?.is_none(),
+    };
+    Ok(if empty { new_true() } else { new_false() })
+}
+/// Return the number of items in the seqable parameter (fully realizing a
+/// lazy sequence).
+fn count(args: Vec<MalValue>) -> MalResult {
+    match collection_len(&args[0]) {
+        Some(len) => Ok(new_integer(len as i32)),
+        None => Ok(new_integer(seq::to_vec(&args[0])?.len() as i32)),
+    }
+}
+/// Return the parameter as a sequence (a list or a lazy sequence), or nil if
+/// it is empty.
+fn seq(args: Vec<MalValue>) -> MalResult {
+    seq::seq(&args[0])
+}
+/// Return the first item of the seqable parameter, nil if it is empty.
+fn first(args: Vec<MalValue>) -> MalResult {
+    match seq::first_rest(&args[0])? {
+        Some((first, _)) => Ok(first),
+        None => Ok(new_nil()),
+    }
+}
+/// Return the seqable parameter without its first item, the empty list if
+/// there is none.
+fn rest(args: Vec<MalValue>) -> MalResult {
+    match seq::first_rest(&args[0])? {
+        Some((_, rest)) => Ok(rest),
+        None => Ok(types::new_list(vec![])),
+    }
+}
+/// Return the seqable parameter without its first item, nil if there is none.
+fn next(args: Vec<MalValue>) -> MalResult {
+    match seq::first_rest(&args[0])? {
+        Some((_, rest)) => seq::seq(&rest),
+        None => Ok(new_nil()),
+    }
+}
+
+/// Return the list (x seq...) for the (x seq) parameters, seq being any
+/// seqable value (the result is lazy if seq is).
+fn cons(args: Vec<MalValue>) -> MalResult {
+    let mut seq = match *args[1] {
+        List(ref seq, _) | Vector(ref seq) => seq.clone(),
+        LazySeq(_) => return Ok(types::new_cons(args[0].clone(), args[1].clone())),
+        _ => seq::to_vec(&args[1])?.into(),
+    };
+    limits::check_size(seq.len() + 1)?;
+    seq.push_front(args[0].clone());
+    Ok(types::new_list(seq))
+}
+/// Return the collection with the items added: at the front of a list, at the
+/// end of a vector, as members of a set, as [key value] entries (or merged
+/// hash maps) of a hash map.
+fn conj(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return err_str(
+            MalErrorKind::Arity,
+            "wrong arity (0) for conj, should be at least 1",
+        );
+    }
+    let size = match *args[0] {
+        List(ref seq, _) | Vector(ref seq) => seq.len(),
+        Set(ref set) => set.len(),
+        Hash(ref hash) => hash.len(),
+        _ => 0,
+    };
+    limits::check_size(size + args.len() - 1)?;
+    let items = args[1..].iter().cloned();
     match *args[0] {
-        List(ref seq) | Vector(ref seq) => match seq.len() {
-            0 => Ok(new_true()),
-            _ => Ok(new_false()),
+        List(ref seq, _) => {
+            let mut seq = seq.clone();
+            items.for_each(|item| seq.push_front(item));
+            Ok(types::new_list(seq))
+        }
+        Nil => Ok(types::new_list(
+            args[1..].iter().rev().cloned().collect::<Vec<_>>(),
+        )),
+        LazySeq(_) => Ok(items.fold(args[0].clone(), |seq, item| types::new_cons(item, seq))),
+        Vector(ref seq) => {
+            let mut seq = seq.clone();
+            seq.extend(items);
+            Ok(types::new_vector(seq))
+        }
+        Set(ref set) => Ok(types::new_set(
+            set.clone().union(items.map(MalHashKey::new).collect()),
+        )),
+        Hash(ref hash) => {
+            let mut hash = hash.clone();
+            for item in items {
+                match *item {
+                    Vector(ref entry) if entry.len() == 2 => {
+                        hash.insert(MalHashKey::new(entry[0].clone()), entry[1].clone());
+                    }
+                    Hash(ref entries) => hash.extend(entries.clone()),
+                    _ => {
+                        return err_str(
+                            MalErrorKind::Type,
+                            "conj on a hash map needs [key value] vectors",
+                        )
+                    }
+                }
+            }
+            Ok(types::new_hash(hash))
+        }
+        _ => err_str(MalErrorKind::Type, "conj called on non-collection"),
+    }
+}
+
+// Lazy sequences
+
+/// Return true if the value is neither nil nor false.
+fn is_truthy(value: &MalValue) -> bool {
+    !matches!(**value, Nil | False)
+}
+
+fn integer_arg(value: &MalValue, function: &str) -> Result<i32, MalError> {
+    match **value {
+        Integer(integer) => Ok(integer),
+        _ => Err(MalError::new(
+            MalErrorKind::Type,
+            format!(
+                "{} expects an integer, got {}",
+                function,
+                value.pr_str(true)
+            ),
+        )),
+    }
+}
+
+fn lazy<F: FnOnce() -> MalResult + 'static>(thunk: F) -> MalValue {
+    types::new_lazy_seq(Box::new(thunk))
+}
+
+fn iterate_seq(f: MalValue, x: MalValue) -> MalValue {
+    types::new_cons(
+        x.clone(),
+        lazy(move || {
+            let next = f.apply(vec![x])?;
+            Ok(iterate_seq(f, next))
+        }),
+    )
+}
+/// Return the infinite lazy sequence (x (f x) (f (f x)) ...).
+fn iterate(args: Vec<MalValue>) -> MalResult {
+    Ok(iterate_seq(args[0].clone(), args[1].clone()))
+}
+
+fn range_seq(start: i32, end: Option<i32>, step: i32) -> MalValue {
+    lazy(move || {
+        let done = match end {
+            Some(end) if step >= 0 => start >= end,
+            Some(end) => start <= end,
+            None => false,
+        };
+        if done {
+            return Ok(new_nil());
+        }
+        Ok(types::new_cons(
+            new_integer(start),
+            match start.checked_add(step) {
+                Some(next) => range_seq(next, end, step),
+                None => new_nil(),
+            },
+        ))
+    })
+}
+/// Return the lazy sequence of the integers from start (inclusive, 0 by
+/// default) to end (exclusive, infinite by default) by step (1 by default).
+fn range(args: Vec<MalValue>) -> MalResult {
+    let integers = args
+        .iter()
+        .map(|arg| integer_arg(arg, "range"))
+        .collect::<Result<Vec<i32>, MalError>>()?;
+    Ok(match integers[..] {
+        [] => range_seq(0, None, 1),
+        [end] => range_seq(0, Some(end), 1),
+        [start, end] => range_seq(start, Some(end), 1),
+        [start, end, step] => range_seq(start, Some(end), step),
+        _ => {
+            return err_string(
+                MalErrorKind::Arity,
+                format!("wrong arity ({}) for range, should be 0 to 3", args.len()),
+            )
+        }
+    })
+}
+
+fn repeat_seq(x: MalValue, n: Option<i32>) -> MalValue {
+    lazy(move || {
+        if n.is_some_and(|n| n <= 0) {
+            return Ok(new_nil());
+        }
+        Ok(types::new_cons(x.clone(), repeat_seq(x, n.map(|n| n - 1))))
+    })
+}
+/// Return the lazy sequence of x repeated infinitely, or n times for the
+/// (n x) parameters.
+fn repeat(args: Vec<MalValue>) -> MalResult {
+    match args.len() {
+        1 => Ok(repeat_seq(args[0].clone(), None)),
+        2 => Ok(repeat_seq(
+            args[1].clone(),
+            Some(integer_arg(&args[0], "repeat")?),
+        )),
+        n => err_string(
+            MalErrorKind::Arity,
+            format!("wrong arity ({}) for repeat, should be 1 or 2", n),
+        ),
+    }
+}
+
+fn take_seq(n: i32, coll: MalValue) -> MalValue {
+    lazy(move || {
+        if n <= 0 {
+            return Ok(new_nil());
+        }
+        Ok(match seq::first_rest(&coll)? {
+            Some((first, rest)) => types::new_cons(first, take_seq(n - 1, rest)),
+            None => new_nil(),
+        })
+    })
+}
+/// Return the lazy sequence of the first n items of the collection.
+fn take(args: Vec<MalValue>) -> MalResult {
+    Ok(take_seq(integer_arg(&args[0], "take")?, args[1].clone()))
+}
+/// Return the lazy sequence of all but the first n items of the collection.
+fn drop(args: Vec<MalValue>) -> MalResult {
+    let n = integer_arg(&args[0], "drop")?;
+    let mut coll = args[1].clone();
+    Ok(lazy(move || {
+        for _ in 0..n {
+            match seq::first_rest(&coll)? {
+                Some((_, rest)) => coll = rest,
+                None => return Ok(new_nil()),
+            }
+        }
+        Ok(coll)
+    }))
+}
+
+fn take_while_seq(pred: MalValue, coll: MalValue) -> MalValue {
+    lazy(move || match seq::first_rest(&coll)? {
+        Some((first, rest)) if is_truthy(&pred.apply(vec![first.clone()])?) => {
+            Ok(types::new_cons(first, take_while_seq(pred, rest)))
+        }
+        _ => Ok(new_nil()),
+    })
+}
+/// Return the lazy sequence of the items of the collection while (pred item)
+/// is true.
+fn take_while(args: Vec<MalValue>) -> MalResult {
+    Ok(take_while_seq(args[0].clone(), args[1].clone()))
+}
+
+fn cycle_seq(coll: MalValue, current: MalValue) -> MalValue {
+    lazy(move || match seq::first_rest(&current)? {
+        Some((first, rest)) => Ok(types::new_cons(first, cycle_seq(coll, rest))),
+        None if seq::first_rest(&coll)?.is_none() => Ok(new_nil()),
+        None => Ok(cycle_seq(coll.clone(), coll)),
+    })
+}
+/// Return the infinite lazy sequence of the items of the collection repeated.
+fn cycle(args: Vec<MalValue>) -> MalResult {
+    Ok(cycle_seq(args[0].clone(), args[0].clone()))
+}
+
+// Higher-order collection operations
+
+/// Return the lazy sequence of (f x...) for the items x... taken in parallel
+/// in the collections, until the shortest one is exhausted.
+fn map_seq(f: MalValue, colls: Vec<MalValue>) -> MalValue {
+    lazy(move || {
+        let mut firsts = Vec::with_capacity(colls.len());
+        let mut rests = Vec::with_capacity(colls.len());
+        for coll in &colls {
+            match seq::first_rest(coll)? {
+                Some((first, rest)) => {
+                    firsts.push(first);
+                    rests.push(rest);
+                }
+                None => return Ok(new_nil()),
+            }
+        }
+        Ok(types::new_cons(f.apply(firsts)?, map_seq(f, rests)))
+    })
+}
+fn map(args: Vec<MalValue>) -> MalResult {
+    if args.len() < 2 {
+        return err_string(
+            MalErrorKind::Arity,
+            format!("wrong arity ({}) for map, should be at least 2", args.len()),
+        );
+    }
+    Ok(map_seq(args[0].clone(), args[1..].to_vec()))
+}
+
+/// Return the lazy sequence of the items for which (pred item) is truthy (if
+/// keep, falsy otherwise).
+fn filter_seq(pred: MalValue, coll: MalValue, keep: bool) -> MalValue {
+    lazy(move || {
+        let mut coll = coll;
+        while let Some((first, rest)) = seq::first_rest(&coll)? {
+            if is_truthy(&pred.apply(vec![first.clone()])?) == keep {
+                return Ok(types::new_cons(first, filter_seq(pred, rest, keep)));
+            }
+            coll = rest;
+        }
+        Ok(new_nil())
+    })
+}
+fn filter(args: Vec<MalValue>) -> MalResult {
+    Ok(filter_seq(args[0].clone(), args[1].clone(), true))
+}
+fn remove(args: Vec<MalValue>) -> MalResult {
+    Ok(filter_seq(args[0].clone(), args[1].clone(), false))
+}
+
+/// Return the lazy concatenation of the current sequence then of the
+/// sequences in colls (a sequence of sequences).
+fn concat_seq(current: MalValue, colls: MalValue) -> MalValue {
+    lazy(move || {
+        let (mut current, mut colls) = (current, colls);
+        loop {
+            if let Some((first, rest)) = seq::first_rest(&current)? {
+                return Ok(types::new_cons(first, concat_seq(rest, colls)));
+            }
+            match seq::first_rest(&colls)? {
+                Some((next, rest)) => {
+                    current = next;
+                    colls = rest;
+                }
+                None => return Ok(new_nil()),
+            }
+        }
+    })
+}
+/// Return the lazy concatenation of the results of (map f colls...).
+fn mapcat(args: Vec<MalValue>) -> MalResult {
+    let mapped = map(args)?;
+    Ok(concat_seq(new_nil(), mapped))
+}
+
+/// Return (f (f (f init x0) x1) x2)... for the (f init coll) parameters, or
+/// for the (f coll) ones with the first item as init ((f) for an empty coll).
+fn reduce(args: Vec<MalValue>) -> MalResult {
+    let (f, mut items, mut acc) = match args.len() {
+        2 => {
+            let mut items = seq::iter(&args[1])?;
+            match items.next() {
+                Some(first) => (&args[0], items, first?),
+                None => return args[0].apply(vec![]),
+            }
+        }
+        3 => (&args[0], seq::iter(&args[2])?, args[1].clone()),
+        n => {
+            return err_string(
+                MalErrorKind::Arity,
+                format!("wrong arity ({}) for reduce, should be 2 or 3", n),
+            )
+        }
+    };
+    for item in &mut items {
+        acc = f.apply(vec![acc, item?])?;
+    }
+    Ok(acc)
+}
+
+/// Sort the items with the given fallible comparison (stable merge sort).
+fn merge_sort<F>(items: Vec<MalValue>, compare: &mut F) -> Result<Vec<MalValue>, MalError>
+where
+    F: FnMut(&MalValue, &MalValue) -> Result<Ordering, MalError>,
+{
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let (left, right) = (merge_sort(left, compare)?, merge_sort(right, compare)?);
+    let mut sorted = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if compare(r, l)? == Ordering::Less {
+            sorted.push(right.next().unwrap());
+        } else {
+            sorted.push(left.next().unwrap());
+        }
+    }
+    sorted.extend(left);
+    sorted.extend(right);
+    Ok(sorted)
+}
+/// Compare a and b with the MAL comparator, returning either an integer
+/// (negative, zero or positive) or a boolean (true if a is before b).
+fn apply_comparator(comp: &MalValue, a: &MalValue, b: &MalValue) -> Result<Ordering, MalError> {
+    match *comp.apply(vec![a.clone(), b.clone()])? {
+        Integer(order) => Ok(order.cmp(&0)),
+        True => Ok(Ordering::Less),
+        False | Nil => Ok(if is_truthy(&comp.apply(vec![b.clone(), a.clone()])?) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }),
+        _ => err_str(
+            MalErrorKind::Type,
+            "comparator must return an integer or a boolean",
+        )
+        .map(|_| Ordering::Equal),
+    }
+}
+/// Return the list of the items sorted by (keyfn item), with the comparator
+/// if any or by the natural ordering of the MAL values otherwise.
+fn sort_items(coll: &MalValue, keyfn: Option<&MalValue>, comp: Option<&MalValue>) -> MalResult {
+    let items = seq::to_vec(coll)?;
+    let mut keyed = Vec::with_capacity(items.len());
+    for item in items {
+        let key = match keyfn {
+            Some(keyfn) => keyfn.apply(vec![item.clone()])?,
+            None => item.clone(),
+        };
+        keyed.push(types::new_vector(vec![key, item]));
+    }
+    let key = |entry: &MalValue| match **entry {
+        Vector(ref entry) => entry[0].clone(),
+        _ => unreachable!(),
+    };
+    let sorted = merge_sort(keyed, &mut |a, b| match comp {
+        Some(comp) => apply_comparator(comp, &key(a), &key(b)),
+        None => types::try_compare_values(&key(a), &key(b)),
+    })?;
+    Ok(types::new_list(
+        sorted
+            .iter()
+            .map(|entry| match **entry {
+                Vector(ref entry) => entry[1].clone(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
+/// Return -1, 0 or 1 if the first value is before, equal to or after the
+/// second one in the natural ordering of the MAL values (the one of sort).
+fn compare(args: Vec<MalValue>) -> MalResult {
+    Ok(new_integer(
+        match types::try_compare_values(&args[0], &args[1])? {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
         },
-        _ => err_str("empty? called on non-list/vector"),
+    ))
+}
+fn sort(args: Vec<MalValue>) -> MalResult {
+    match args.len() {
+        1 => sort_items(&args[0], None, None),
+        2 => sort_items(&args[1], None, Some(&args[0])),
+        n => err_string(
+            MalErrorKind::Arity,
+            format!("wrong arity ({}) for sort, should be 1 or 2", n),
+        ),
     }
 }
-/// Return the number of items in the List/Vector parameter.
-fn count(args: Vec<MalValue>) -> MalResult {
+fn sort_by(args: Vec<MalValue>) -> MalResult {
+    match args.len() {
+        2 => sort_items(&args[1], Some(&args[0]), None),
+        3 => sort_items(&args[2], Some(&args[0]), Some(&args[1])),
+        n => err_string(
+            MalErrorKind::Arity,
+            format!("wrong arity ({}) for sort-by, should be 2 or 3", n),
+        ),
+    }
+}
+
+/// Return the hash map of (f item) to the vector of the corresponding items.
+fn group_by(args: Vec<MalValue>) -> MalResult {
+    let mut groups = types::MalHashContainer::new();
+    for item in seq::iter(&args[1])? {
+        let item = item?;
+        let key = MalHashKey::new(args[0].apply(vec![item.clone()])?);
+        let mut group = match groups.get(&key).map(|group| &**group) {
+            Some(Vector(group)) => group.clone(),
+            _ => types::MalList::new(),
+        };
+        group.push_back(item);
+        limits::check_size(group.len())?;
+        groups.insert(key, types::new_vector(group));
+        limits::check_size(groups.len())?;
+    }
+    Ok(types::new_hash(groups))
+}
+/// Return the hash map of the distinct items to their number of occurrences.
+fn frequencies(args: Vec<MalValue>) -> MalResult {
+    let mut counts = types::MalHashContainer::new();
+    for item in seq::iter(&args[0])? {
+        let key = MalHashKey::new(item?);
+        let count = match counts.get(&key).map(|count| &**count) {
+            Some(&Integer(count)) => count + 1,
+            _ => 1,
+        };
+        counts.insert(key, new_integer(count));
+        limits::check_size(counts.len())?;
+    }
+    Ok(types::new_hash(counts))
+}
+
+fn partition_seq(n: i32, step: i32, coll: MalValue) -> MalValue {
+    lazy(move || {
+        let mut partition = vec![];
+        let mut items = seq::iter(&coll)?;
+        while partition.len() < n as usize {
+            match items.next() {
+                Some(item) => {
+                    limits::check_size(partition.len() + 1)?;
+                    partition.push(item?)
+                }
+                // an incomplete partition is dropped
+                None => return Ok(new_nil()),
+            }
+        }
+        let mut rest = coll;
+        for _ in 0..step {
+            match seq::first_rest(&rest)? {
+                Some((_, next)) => rest = next,
+                None => break,
+            }
+        }
+        Ok(types::new_cons(
+            types::new_list(partition),
+            partition_seq(n, step, rest),
+        ))
+    })
+}
+/// Return the lazy sequence of the lists of n items of the collection, with
+/// offsets of step items (n by default).
+fn partition(args: Vec<MalValue>) -> MalResult {
+    let (n, step, coll) = match args.len() {
+        2 => {
+            let n = integer_arg(&args[0], "partition")?;
+            (n, n, &args[1])
+        }
+        3 => (
+            integer_arg(&args[0], "partition")?,
+            integer_arg(&args[1], "partition")?,
+            &args[2],
+        ),
+        n => {
+            return err_string(
+                MalErrorKind::Arity,
+                format!("wrong arity ({}) for partition, should be 2 or 3", n),
+            )
+        }
+    };
+    if n <= 0 || step <= 0 {
+        return err_str(MalErrorKind::Value, "partition needs positive sizes");
+    }
+    Ok(partition_seq(n, step, coll.clone()))
+}
+
+fn interleave_seq(colls: Vec<MalValue>) -> MalValue {
+    lazy(move || {
+        let mut firsts = Vec::with_capacity(colls.len());
+        let mut rests = Vec::with_capacity(colls.len());
+        for coll in &colls {
+            match seq::first_rest(coll)? {
+                Some((first, rest)) => {
+                    firsts.push(first);
+                    rests.push(rest);
+                }
+                None => return Ok(new_nil()),
+            }
+        }
+        Ok(firsts
+            .into_iter()
+            .rev()
+            .fold(interleave_seq(rests), |seq, first| {
+                types::new_cons(first, seq)
+            }))
+    })
+}
+/// Return the lazy sequence of the first items of the collections, then of
+/// the second ones... until the shortest collection is exhausted.
+fn interleave(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return Ok(types::new_list(vec![]));
+    }
+    Ok(interleave_seq(args))
+}
+
+fn distinct_seq(coll: MalValue, seen: MalSetContainer) -> MalValue {
+    lazy(move || {
+        let mut coll = coll;
+        while let Some((first, rest)) = seq::first_rest(&coll)? {
+            let key = MalHashKey::new(first.clone());
+            if !seen.contains(&key) {
+                return Ok(types::new_cons(first, distinct_seq(rest, seen.update(key))));
+            }
+            coll = rest;
+        }
+        Ok(new_nil())
+    })
+}
+/// Return the lazy sequence of the items without duplicates.
+fn distinct(args: Vec<MalValue>) -> MalResult {
+    Ok(distinct_seq(args[0].clone(), MalSetContainer::new()))
+}
+
+/// Return the first truthy (pred item), nil if none.
+fn some(args: Vec<MalValue>) -> MalResult {
+    for item in seq::iter(&args[1])? {
+        let result = args[0].apply(vec![item?])?;
+        if is_truthy(&result) {
+            return Ok(result);
+        }
+    }
+    Ok(new_nil())
+}
+/// Return true if (pred item) is truthy for all the items, false otherwise.
+fn every_q(args: Vec<MalValue>) -> MalResult {
+    for item in seq::iter(&args[1])? {
+        if !is_truthy(&args[0].apply(vec![item?])?) {
+            return Ok(new_false());
+        }
+    }
+    Ok(new_true())
+}
+
+/// Return the first collection with all the items of the second one conj'ed.
+fn into(args: Vec<MalValue>) -> MalResult {
+    let mut conj_args = vec![args[0].clone()];
+    for item in seq::iter(&args[1])? {
+        limits::check_size(conj_args.len())?;
+        conj_args.push(item?);
+    }
+    conj(conj_args)
+}
+/// Return the hash map of the keys to the corresponding values.
+fn zipmap(args: Vec<MalValue>) -> MalResult {
+    let mut hash = types::MalHashContainer::new();
+    for (key, value) in seq::iter(&args[0])?.zip(seq::iter(&args[1])?) {
+        hash.insert(MalHashKey::new(key?), value?);
+        limits::check_size(hash.len())?;
+    }
+    Ok(types::new_hash(hash))
+}
+
+/// Return the list of the items in reverse order.
+fn reverse(args: Vec<MalValue>) -> MalResult {
+    let mut items = seq::to_vec(&args[0])?;
+    items.reverse();
+    Ok(types::new_list(items))
+}
+/// Return the last item, nil if there is none.
+fn last(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        List(ref seq, _) | Vector(ref seq) => Ok(seq.last().cloned().unwrap_or_else(new_nil)),
+        _ => Ok(seq::to_vec(&args[0])?.pop().unwrap_or_else(new_nil)),
+    }
+}
+/// Return the list of all the items but the last one, nil if there is none.
+fn butlast(args: Vec<MalValue>) -> MalResult {
+    let mut items: types::MalList = match *args[0] {
+        List(ref seq, _) | Vector(ref seq) => seq.clone(),
+        _ => seq::to_vec(&args[0])?.into(),
+    };
+    if items.len() <= 1 {
+        return Ok(new_nil());
+    }
+    items.pop_back();
+    Ok(types::new_list(items))
+}
+
+// Associative operations
+
+/// Return the hash map (or vector) with the given (key value...) pairs associated.
+fn assoc(args: Vec<MalValue>) -> MalResult {
+    if args.len() % 2 != 1 {
+        return err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for assoc, should be a collection then key/value pairs",
+                args.len()
+            ),
+        );
+    }
+    let pairs = args[1..].chunks(2);
+    let size = match *args[0] {
+        Hash(ref hash) => hash.len(),
+        Vector(ref seq) => seq.len(),
+        _ => 0,
+    };
+    limits::check_size(size + pairs.len())?;
+    match *args[0] {
+        Hash(ref hash) => {
+            let mut hash = hash.clone();
+            for pair in pairs {
+                hash.insert(MalHashKey::new(pair[0].clone()), pair[1].clone());
+            }
+            Ok(types::new_hash(hash))
+        }
+        Nil => {
+            let mut hash = types::MalHashContainer::new();
+            for pair in pairs {
+                hash.insert(MalHashKey::new(pair[0].clone()), pair[1].clone());
+            }
+            Ok(types::new_hash(hash))
+        }
+        Vector(ref seq) => {
+            let mut seq = seq.clone();
+            for pair in pairs {
+                match *pair[0] {
+                    Integer(i) if i >= 0 && i as usize <= seq.len() => {
+                        if i as usize == seq.len() {
+                            seq.push_back(pair[1].clone());
+                        } else {
+                            seq.set(i as usize, pair[1].clone());
+                        }
+                    }
+                    _ => {
+                        return err_str(
+                            MalErrorKind::Value,
+                            "assoc on a vector needs an index within bounds",
+                        )
+                    }
+                }
+            }
+            Ok(types::new_vector(seq))
+        }
+        _ => err_str(MalErrorKind::Type, "assoc called on non-hash-map/vector"),
+    }
+}
+/// Return the hash map without the given keys.
+fn dissoc(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return err_str(
+            MalErrorKind::Arity,
+            "wrong arity (0) for dissoc, should be at least 1",
+        );
+    }
     match *args[0] {
-        List(ref seq) | Vector(ref seq) => Ok(new_integer(seq.len() as i32)),
-        Nil => Ok(new_integer(0)),
-        _ => err_str("count called on non-list/vector"),
+        Hash(ref hash) => {
+            let mut hash = hash.clone();
+            for key in &args[1..] {
+                hash.remove(&MalHashKey::new(key.clone()));
+            }
+            Ok(types::new_hash(hash))
+        }
+        Nil => Ok(new_nil()),
+        _ => err_str(MalErrorKind::Type, "dissoc called on non-hash-map"),
+    }
+}
+/// Return the value associated to the key in the hash map (or at the index in
+/// the vector), nil if none.
+fn get(args: Vec<MalValue>) -> MalResult {
+    match (&*args[0], &*args[1]) {
+        (Hash(hash), _) => Ok(hash
+            .get(&MalHashKey::new(args[1].clone()))
+            .cloned()
+            .unwrap_or_else(new_nil)),
+        (Vector(seq), &Integer(i)) if i >= 0 => {
+            Ok(seq.get(i as usize).cloned().unwrap_or_else(new_nil))
+        }
+        (Nil, _) | (Vector(_), _) => Ok(new_nil()),
+        _ => err_str(MalErrorKind::Type, "get called on non-hash-map/vector"),
+    }
+}
+
+/// Return true if the key is present in the hash map (or the set, or is an
+/// index of the vector), false otherwise.
+fn contains_q(args: Vec<MalValue>) -> MalResult {
+    let key = MalHashKey::new(args[1].clone());
+    let contained = match (&*args[0], &*args[1]) {
+        (Hash(hash), _) => hash.contains_key(&key),
+        (Set(set), _) => set.contains(&key),
+        (Vector(seq), &Integer(i)) => i >= 0 && (i as usize) < seq.len(),
+        (Nil, _) | (Vector(_), _) => false,
+        _ => {
+            return err_str(
+                MalErrorKind::Type,
+                "contains? called on non-hash-map/set/vector",
+            )
+        }
+    };
+    Ok(if contained { new_true() } else { new_false() })
+}
+
+// Set operations
+
+/// Return the set parameter, nil being considered as the empty set.
+fn set_arg(value: &MalValue, function: &str) -> Result<MalSetContainer, MalError> {
+    match **value {
+        Set(ref set) => Ok(set.clone()),
+        Nil => Ok(MalSetContainer::new()),
+        _ => Err(MalError::new(
+            MalErrorKind::Type,
+            format!("{} called on non-set", function),
+        )),
     }
 }
 
+/// Create and return the set #{args...}.
+fn hash_set(args: Vec<MalValue>) -> MalResult {
+    limits::check_size(args.len())?;
+    Ok(types::new_set(
+        args.into_iter().map(MalHashKey::new).collect(),
+    ))
+}
+/// Return the set of the distinct items in the collection parameter (the
+/// [key value] entries for a hash map).
+fn set(args: Vec<MalValue>) -> MalResult {
+    let set: MalSetContainer = match *args[0] {
+        List(ref seq, _) | Vector(ref seq) => seq.iter().cloned().map(MalHashKey::new).collect(),
+        Hash(ref hash) => hash
+            .iter()
+            .map(|(k, v)| MalHashKey::new(types::new_vector(vec![k.value().clone(), v.clone()])))
+            .collect(),
+        Set(ref set) => set.clone(),
+        Nil => MalSetContainer::new(),
+        _ => return err_str(MalErrorKind::Type, "set called on non-collection"),
+    };
+    limits::check_size(set.len())?;
+    Ok(types::new_set(set))
+}
+/// Return true if the parameter is a set, false otherwise.
+fn set_q(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        Set(_) => Ok(new_true()),
+        _ => Ok(new_false()),
+    }
+}
+/// Return the set without the given items (nil for nil).
+fn disj(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return err_str(
+            MalErrorKind::Arity,
+            "wrong arity (0) for disj, should be at least 1",
+        );
+    }
+    if let Nil = *args[0] {
+        return Ok(new_nil());
+    }
+    let mut set = set_arg(&args[0], "disj")?;
+    for item in &args[1..] {
+        set.remove(&MalHashKey::new(item.clone()));
+    }
+    Ok(types::new_set(set))
+}
+/// Return the set of the items in any of the given sets.
+fn union(args: Vec<MalValue>) -> MalResult {
+    let mut union = MalSetContainer::new();
+    for arg in &args {
+        union = union.union(set_arg(arg, "union")?);
+        limits::check_size(union.len())?;
+    }
+    Ok(types::new_set(union))
+}
+/// Return the set of the items of the first set also in all the other ones.
+fn intersection(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return err_str(
+            MalErrorKind::Arity,
+            "wrong arity (0) for intersection, should be at least 1",
+        );
+    }
+    let mut intersection = set_arg(&args[0], "intersection")?;
+    for arg in &args[1..] {
+        intersection = intersection.intersection(set_arg(arg, "intersection")?);
+    }
+    Ok(types::new_set(intersection))
+}
+/// Return the set of the items of the first set in none of the other ones.
+fn difference(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return err_str(
+            MalErrorKind::Arity,
+            "wrong arity (0) for difference, should be at least 1",
+        );
+    }
+    let mut difference = set_arg(&args[0], "difference")?;
+    for arg in &args[1..] {
+        difference = difference.relative_complement(set_arg(arg, "difference")?);
+    }
+    Ok(types::new_set(difference))
+}
+/// Return true if all the items of the first set are in the second one.
+fn subset_q(args: Vec<MalValue>) -> MalResult {
+    let subset = set_arg(&args[0], "subset?")?;
+    if subset.is_subset(set_arg(&args[1], "subset?")?) {
+        Ok(new_true())
+    } else {
+        Ok(new_false())
+    }
+}
+
+// String operations
+
+/// Return the string parameter of the given function.
+fn str_arg<'a>(value: &'a MalValue, function: &str) -> Result<&'a str, MalError> {
+    match **value {
+        Str(ref string) => Ok(string),
+        _ => Err(MalError::new(
+            MalErrorKind::Type,
+            format!("{} expects a string, got {}", function, value.pr_str(true)),
+        )),
+    }
+}
+
+/// Return the printed representations of the values joined by sep.
+fn pr_values(values: &[MalValue], print_readably: bool, sep: &str) -> Result<String, MalError> {
+    let mut printed = String::new();
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            printed.push_str(sep);
+        }
+        printed.push_str(&value.pr_str(print_readably));
+        limits::check_length(&printed)?;
+    }
+    Ok(printed)
+}
+
+/// Return the string value, failing if it exceeds the collection size limit.
+fn checked_str(string: String) -> MalResult {
+    limits::check_length(&string)?;
+    Ok(new_str(string))
+}
+
+/// Return the concatenation of the non-readable representations of args.
+fn str(args: Vec<MalValue>) -> MalResult {
+    Ok(new_str(pr_values(&args, false, "")?))
+}
+/// Return the readable representations of args separated by spaces.
+fn pr_str(args: Vec<MalValue>) -> MalResult {
+    Ok(new_str(pr_values(&args, true, " ")?))
+}
+/// Print the readable representations of args separated by spaces.
+fn prn(args: Vec<MalValue>) -> MalResult {
+    println!("{}", pr_values(&args, true, " ")?);
+    Ok(new_nil())
+}
+/// Print the non-readable representations of args separated by spaces.
+fn println(args: Vec<MalValue>) -> MalResult {
+    println!("{}", pr_values(&args, false, " ")?);
+    Ok(new_nil())
+}
+
+/// Return the (start end) parameters of subs as character indices.
+fn subs_range(args: &[MalValue], len: usize) -> Result<(usize, usize), MalError> {
+    let start = integer_arg(&args[1], "subs")?;
+    let end = match args.get(2) {
+        Some(end) => integer_arg(end, "subs")?,
+        None => len as i32,
+    };
+    if start < 0 || end < start || end as usize > len {
+        return Err(MalError::new(
+            MalErrorKind::Value,
+            format!(
+                "subs range ({} {}) out of bounds for a string of length {}",
+                start, end, len
+            ),
+        ));
+    }
+    Ok((start as usize, end as usize))
+}
+/// Return the substring between the start (inclusive) and end (exclusive,
+/// the end of the string by default) character indices.
+fn subs(args: Vec<MalValue>) -> MalResult {
+    if args.len() < 2 || args.len() > 3 {
+        return err_string(
+            MalErrorKind::Arity,
+            format!("wrong arity ({}) for subs, should be 2 or 3", args.len()),
+        );
+    }
+    let string = str_arg(&args[0], "subs")?;
+    let (start, end) = subs_range(&args, string.chars().count())?;
+    Ok(new_str(
+        string.chars().skip(start).take(end - start).collect(),
+    ))
+}
+/// Return the vector of the parts of the string around the separator (of its
+/// characters for an empty separator).
+fn split(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "split")?;
+    let separator = str_arg(&args[1], "split")?;
+    let parts: Vec<MalValue> = if separator.is_empty() {
+        string.chars().map(|c| new_str(c.to_string())).collect()
+    } else {
+        string
+            .split(separator)
+            .map(types::new_str_from_slice)
+            .collect()
+    };
+    limits::check_size(parts.len())?;
+    Ok(types::new_vector(parts))
+}
+/// Return the concatenation of the non-readable representations of the items
+/// of the collection, separated by the optional separator.
+fn join(args: Vec<MalValue>) -> MalResult {
+    let (separator, coll) = match args.len() {
+        1 => ("", &args[0]),
+        2 => (str_arg(&args[0], "join")?, &args[1]),
+        n => {
+            return err_string(
+                MalErrorKind::Arity,
+                format!("wrong arity ({}) for join, should be 1 or 2", n),
+            )
+        }
+    };
+    let mut joined = String::new();
+    for (i, item) in seq::iter(coll)?.enumerate() {
+        if i > 0 {
+            joined.push_str(separator);
+        }
+        joined.push_str(&item?.pr_str(false));
+        limits::check_length(&joined)?;
+    }
+    Ok(new_str(joined))
+}
+fn upper_case(args: Vec<MalValue>) -> MalResult {
+    checked_str(str_arg(&args[0], "upper-case")?.to_uppercase())
+}
+fn lower_case(args: Vec<MalValue>) -> MalResult {
+    checked_str(str_arg(&args[0], "lower-case")?.to_lowercase())
+}
+/// Return the string without its leading and trailing whitespace.
+fn trim(args: Vec<MalValue>) -> MalResult {
+    Ok(types::new_str_from_slice(str_arg(&args[0], "trim")?.trim()))
+}
+fn starts_with_q(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "starts-with?")?;
+    let prefix = str_arg(&args[1], "starts-with?")?;
+    Ok(if string.starts_with(prefix) {
+        new_true()
+    } else {
+        new_false()
+    })
+}
+fn ends_with_q(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "ends-with?")?;
+    let suffix = str_arg(&args[1], "ends-with?")?;
+    Ok(if string.ends_with(suffix) {
+        new_true()
+    } else {
+        new_false()
+    })
+}
+fn includes_q(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "includes?")?;
+    let substring = str_arg(&args[1], "includes?")?;
+    Ok(if string.contains(substring) {
+        new_true()
+    } else {
+        new_false()
+    })
+}
+/// Return the character index of the first occurrence of the substring, nil
+/// if there is none.
+fn index_of(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "index-of")?;
+    let substring = str_arg(&args[1], "index-of")?;
+    Ok(match string.find(substring) {
+        Some(byte_index) => new_integer(string[..byte_index].chars().count() as i32),
+        None => new_nil(),
+    })
+}
+/// Return the string with all the occurrences of match replaced.
+fn replace(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "replace")?;
+    let pattern = str_arg(&args[1], "replace")?;
+    let replacement = str_arg(&args[2], "replace")?;
+    if pattern.is_empty() {
+        return err_str(MalErrorKind::Value, "replace with an empty match");
+    }
+    checked_str(string.replace(pattern, replacement))
+}
+/// Return the character at the given character index.
+fn char_at(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "char-at")?;
+    let index = integer_arg(&args[1], "char-at")?;
+    let c = if index < 0 {
+        None
+    } else {
+        string.chars().nth(index as usize)
+    };
+    match c {
+        Some(c) => Ok(types::new_char(c)),
+        None => err_string(
+            MalErrorKind::Value,
+            format!(
+                "char-at index {} out of bounds for a string of length {}",
+                index,
+                string.chars().count()
+            ),
+        ),
+    }
+}
+/// Return the list of the characters of the string.
+fn string_to_list(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[0], "string->list")?;
+    limits::check_size(string.chars().count())?;
+    Ok(types::new_list(
+        string.chars().map(types::new_char).collect::<Vec<_>>(),
+    ))
+}
+
+/// Pad the formatted value up to width characters (on the right if left
+/// aligned, with zeros if zero padded and numeric).
+fn pad(formatted: String, width: usize, left: bool, zero: bool) -> String {
+    let len = formatted.chars().count();
+    if len >= width {
+        return formatted;
+    }
+    let padding = width - len;
+    if left {
+        format!("{}{}", formatted, " ".repeat(padding))
+    } else if zero {
+        match formatted.strip_prefix('-') {
+            Some(digits) => format!("-{}{}", "0".repeat(padding), digits),
+            None => format!("{}{}", "0".repeat(padding), formatted),
+        }
+    } else {
+        format!("{}{}", " ".repeat(padding), formatted)
+    }
+}
+/// The maximum width or precision of a format directive.
+const MAX_FORMAT_SIZE: usize = 1 << 16;
+/// Read the width or precision of a format directive (0 if none), failing if
+/// it exceeds 'MAX_FORMAT_SIZE'.
+fn format_size(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    name: &str,
+) -> Result<usize, MalError> {
+    let mut size: usize = 0;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        size = size
+            .checked_mul(10)
+            .and_then(|size| size.checked_add(digit as usize))
+            .filter(|&size| size <= MAX_FORMAT_SIZE)
+            .ok_or_else(|| {
+                MalError::new(
+                    MalErrorKind::Value,
+                    format!("format: {} larger than {}", name, MAX_FORMAT_SIZE),
+                )
+            })?;
+        chars.next();
+    }
+    Ok(size)
+}
+/// Return the format string with its printf-style directives replaced by the
+/// formatted arguments: %[-][0][width][.precision]conversion, where the
+/// conversion is s (any value, as by str), d (integer), x / X (hexadecimal
+/// integer), o (octal integer) or c (character); %% is a literal %.
+fn format(args: Vec<MalValue>) -> MalResult {
+    if args.is_empty() {
+        return err_str(
+            MalErrorKind::Arity,
+            "wrong arity (0) for format, should be at least 1",
+        );
+    }
+    let template = str_arg(&args[0], "format")?;
+    let mut values = args[1..].iter();
+    let mut formatted = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        let (mut left, mut zero) = (false, false);
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => left = true,
+                '0' => zero = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        let width = format_size(&mut chars, "width")?;
+        let mut precision = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            precision = Some(format_size(&mut chars, "precision")?);
+        }
+        let conversion = match chars.next() {
+            Some('%') => {
+                formatted.push('%');
+                continue;
+            }
+            Some(conversion) => conversion,
+            None => {
+                return err_str(
+                    MalErrorKind::Value,
+                    "format string ends in the middle of a directive",
+                )
+            }
+        };
+        let value = match values.next() {
+            Some(value) => value,
+            None => {
+                return err_string(
+                    MalErrorKind::Arity,
+                    format!("format: missing argument for %{}", conversion),
+                )
+            }
+        };
+        let integer = || integer_arg(value, "format");
+        let field = match conversion {
+            's' => {
+                let string = value.pr_str(false);
+                match precision {
+                    Some(precision) => string.chars().take(precision).collect(),
+                    None => string,
+                }
+            }
+            'd' => integer()?.to_string(),
+            'x' => format!("{:x}", integer()?),
+            'X' => format!("{:X}", integer()?),
+            'o' => format!("{:o}", integer()?),
+            'c' => match **value {
+                Char(c) => c.to_string(),
+                _ => {
+                    return err_string(
+                        MalErrorKind::Type,
+                        format!("format: %c expects a character, got {}", value.pr_str(true)),
+                    )
+                }
+            },
+            _ => {
+                return err_string(
+                    MalErrorKind::Value,
+                    format!("format: unknown conversion %{}", conversion),
+                )
+            }
+        };
+        let numeric = matches!(conversion, 'd' | 'x' | 'X' | 'o');
+        formatted.push_str(&pad(field, width, left, zero && numeric));
+        limits::check_length(&formatted)?;
+    }
+    if values.next().is_some() {
+        return err_str(
+            MalErrorKind::Arity,
+            "format: too many arguments for the format string",
+        );
+    }
+    Ok(new_str(formatted))
+}
+
+// Character operations
+
+/// Return the character of the given code point (or the given character).
+fn char(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        Char(_) => Ok(args[0].clone()),
+        Integer(code) => match std::char::from_u32(code as u32) {
+            Some(c) if code >= 0 => Ok(types::new_char(c)),
+            _ => err_string(
+                MalErrorKind::Value,
+                format!("char: invalid code point {}", code),
+            ),
+        },
+        _ => err_string(
+            MalErrorKind::Type,
+            format!(
+                "char expects an integer or a character, got {}",
+                args[0].pr_str(true)
+            ),
+        ),
+    }
+}
+/// Return true if the parameter is a character, false otherwise.
+fn char_q(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        Char(_) => Ok(new_true()),
+        _ => Ok(new_false()),
+    }
+}
+/// Return the code point of the given character (or the given integer).
+fn int(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        Char(c) => Ok(new_integer(c as i32)),
+        Integer(_) => Ok(args[0].clone()),
+        _ => err_string(
+            MalErrorKind::Type,
+            format!(
+                "int expects a character or an integer, got {}",
+                args[0].pr_str(true)
+            ),
+        ),
+    }
+}
+
+// Regex operations
+
+/// Return the regex parameter of the given function, compiling it if given
+/// as a string.
+fn regex_arg(value: &MalValue, function: &str) -> Result<regex::Regex, MalError> {
+    match **value {
+        Regex(ref regex) => Ok((**regex).clone()),
+        Str(ref pattern) => regex::Regex::new(pattern).map_err(|why| {
+            MalError::new(
+                MalErrorKind::Value,
+                format!("{}: invalid regex: {}", function, why),
+            )
+        }),
+        _ => Err(MalError::new(
+            MalErrorKind::Type,
+            format!("{} expects a regex, got {}", function, value.pr_str(true)),
+        )),
+    }
+}
+
+/// Return the MAL value of a match: the matched string if the regex has no
+/// capture groups, otherwise the vector of the matched string and of the
+/// groups (nil for the unmatched ones).
+fn match_value(captures: &regex::Captures) -> MalValue {
+    if captures.len() == 1 {
+        return types::new_str_from_slice(&captures[0]);
+    }
+    types::new_vector(
+        captures
+            .iter()
+            .map(|group| match group {
+                Some(group) => types::new_str_from_slice(group.as_str()),
+                None => new_nil(),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Return the regex compiled from the string (or the given regex).
+fn re_pattern(args: Vec<MalValue>) -> MalResult {
+    Ok(types::new_regex(regex_arg(&args[0], "re-pattern")?))
+}
+/// Return the first match of the regex in the string, nil if there is none.
+fn re_find(args: Vec<MalValue>) -> MalResult {
+    let regex = regex_arg(&args[0], "re-find")?;
+    let string = str_arg(&args[1], "re-find")?;
+    Ok(regex
+        .captures(string)
+        .map(|captures| match_value(&captures))
+        .unwrap_or_else(new_nil))
+}
+/// Return the match of the regex against the whole string, nil if it does
+/// not match.
+fn re_matches(args: Vec<MalValue>) -> MalResult {
+    let string = str_arg(&args[1], "re-matches")?;
+    let invalid = |why| MalError::new(MalErrorKind::Value, format!("re-matches: {}", why));
+    // the anchored form of a regex value is only compiled once
+    let anchored = match *args[0] {
+        Regex(ref regex) => Cow::Borrowed(regex.anchored().map_err(invalid)?),
+        _ => Cow::Owned(types::anchor_regex(&regex_arg(&args[0], "re-matches")?).map_err(invalid)?),
+    };
+    Ok(anchored
+        .captures(string)
+        .map(|captures| match_value(&captures))
+        .unwrap_or_else(new_nil))
+}
+/// Return the list of the successive matches of the regex in the string, nil
+/// if there is none.
+fn re_seq(args: Vec<MalValue>) -> MalResult {
+    let regex = regex_arg(&args[0], "re-seq")?;
+    let string = str_arg(&args[1], "re-seq")?;
+    let mut matches = vec![];
+    for captures in regex.captures_iter(string) {
+        limits::check_size(matches.len() + 1)?;
+        matches.push(match_value(&captures));
+    }
+    Ok(if matches.is_empty() {
+        new_nil()
+    } else {
+        types::new_list(matches)
+    })
+}
+/// Return the string with all the matches of the regex replaced, either by
+/// a string (where `$1` or `${name}` refer to the capture groups) or by the
+/// result of a function called on each match.
+fn re_replace(args: Vec<MalValue>) -> MalResult {
+    let regex = regex_arg(&args[0], "re-replace")?;
+    let string = str_arg(&args[1], "re-replace")?;
+    match *args[2] {
+        Str(ref replacement) => {
+            checked_str(regex.replace_all(string, &replacement[..]).into_owned())
+        }
+        Function(_) | MalFunction(_) => {
+            let mut replaced = String::with_capacity(string.len());
+            let mut last = 0;
+            for captures in regex.captures_iter(string) {
+                let whole = captures.get(0).unwrap();
+                replaced.push_str(&string[last..whole.start()]);
+                match *args[2].apply(vec![match_value(&captures)])? {
+                    Str(ref replacement) => {
+                        replaced.push_str(replacement);
+                        limits::check_length(&replaced)?;
+                    }
+                    _ => {
+                        return err_str(
+                            MalErrorKind::Type,
+                            "re-replace function must return a string",
+                        )
+                    }
+                }
+                last = whole.end();
+            }
+            replaced.push_str(&string[last..]);
+            Ok(new_str(replaced))
+        }
+        _ => err_str(
+            MalErrorKind::Type,
+            "re-replace expects a string or a function as replacement",
+        ),
+    }
+}
+
+// Function metadata
+
+/// Return the function parameter of the given function: its data if defined
+/// in MAL, None if native.
+fn function_arg<'a>(
+    value: &'a MalValue,
+    function: &str,
+) -> Result<Option<&'a MalFunctionData>, MalError> {
+    match **value {
+        MalFunction(ref data) => Ok(Some(data)),
+        Function(_) => Ok(None),
+        _ => Err(MalError::new(
+            MalErrorKind::Type,
+            format!(
+                "{} expects a function, got {}",
+                function,
+                value.pr_str(true)
+            ),
+        )),
+    }
+}
+/// Return the docstring the function was defined with, nil if none.
+fn doc(args: Vec<MalValue>) -> MalResult {
+    Ok(
+        match function_arg(&args[0], "doc")?.and_then(|data| data.doc()) {
+            Some(doc) => new_str(doc.to_string()),
+            None => new_nil(),
+        },
+    )
+}
+/// Return the list of the parameters lists of the function, nil if native.
+fn arglists(args: Vec<MalValue>) -> MalResult {
+    Ok(match function_arg(&args[0], "arglists")? {
+        Some(data) => types::new_list(data.arglists().cloned().collect::<Vec<_>>()),
+        None => new_nil(),
+    })
+}
+/// Return the text of the fn* (or defn) form defining the function, nil if
+/// unknown.
+fn source(args: Vec<MalValue>) -> MalResult {
+    Ok(
+        match function_arg(&args[0], "source")?.and_then(|data| data.source()) {
+            Some(source) => new_str(source.as_str().to_string()),
+            None => new_nil(),
+        },
+    )
+}
+
+// Errors
+
+/// Raise a user error, of the given string as its message (or of the printed
+/// representation of any other value).
+fn throw(args: Vec<MalValue>) -> MalResult {
+    let message = match *args[0] {
+        Str(ref string) => string.clone(),
+        _ => args[0].pr_str(true),
+    };
+    err_string(MalErrorKind::User, message)
+}
+
+// Memory management
+
+/// Return the given memory statistics as a hash map.
+fn stats_hash(stats: gc::MemoryStats) -> MalValue {
+    let mut hash = types::MalHashContainer::new();
+    for (key, value) in &[
+        ("envs", stats.envs),
+        ("collections", stats.collections),
+        ("collected", stats.collected),
+    ] {
+        hash.insert(
+            MalHashKey::new(types::new_keyword(key.to_string())),
+            new_integer(*value as i32),
+        );
+    }
+    types::new_hash(hash)
+}
+/// Collect the garbage cycles of environments and functions, and return the
+/// memory statistics.
+fn gc(_args: Vec<MalValue>) -> MalResult {
+    gc::collect();
+    Ok(stats_hash(gc::stats()))
+}
+/// Return the memory statistics: the number of live environments, of
+/// collections run and of environments they cleared.
+fn memory_stats(_args: Vec<MalValue>) -> MalResult {
+    Ok(stats_hash(gc::stats()))
+}
+
+// File operations
+
+/// Return the content of the file of the given path, as a string.
+fn slurp(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        Str(ref path) => match fs::read_to_string(path) {
+            Ok(content) => Ok(new_str(content)),
+            Err(why) => err_string(
+                MalErrorKind::Value,
+                format!("slurp: cannot read {}: {}", path, why),
+            ),
+        },
+        _ => err_str(MalErrorKind::Type, "slurp called on non-string"),
+    }
+}
+
+// EDN operations
+
+/// Read the EDN string parameter into a MAL value, without evaluating it.
+fn edn_read_string(args: Vec<MalValue>) -> MalResult {
+    match *args[0] {
+        Str(ref string) => edn::read_edn(string),
+        _ => err_str(MalErrorKind::Type, "edn-read-string called on non-string"),
+    }
+}
+/// Return the EDN representation of the parameter.
+fn edn_str(args: Vec<MalValue>) -> MalResult {
+    edn::write_edn(&args[0]).map(new_str)
+}
+
 // Integer operations
 
 fn int_op<F>(f: F, args: Vec<MalValue>) -> MalResult
@@ -57,17 +1593,20 @@ where
     F: FnOnce(i32, i32) -> i32,
 {
     if args.len() != 2 {
-        return err_string(format!(
-            "wrong arity ({}) for operation between 2 integers",
-            args.len()
-        ));
+        return err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for operation between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         Integer(left) => match *args[1] {
             Integer(right) => Ok(new_integer(f(left, right))),
-            _ => err_str("right argument must be an integer"),
+            _ => err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => err_str("left argument must be an integer"),
+        _ => err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 fn add(args: Vec<MalValue>) -> MalResult {
@@ -82,23 +1621,26 @@ fn mul(args: Vec<MalValue>) -> MalResult {
 
 fn div(args: Vec<MalValue>) -> MalResult {
     if args.len() != 2 {
-        return err_string(format!(
-            "wrong arity ({}) for operation between 2 integers",
-            args.len()
-        ));
+        return err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for operation between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         Integer(left) => match *args[1] {
             Integer(right) => {
                 if right == 0 {
-                    err_str("cannot divide by 0")
+                    err_str(MalErrorKind::DivisionByZero, "cannot divide by 0")
                 } else {
                     Ok(new_integer(left / right))
                 }
             }
-            _ => err_str("right argument must be an integer"),
+            _ => err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => err_str("left argument must be an integer"),
+        _ => err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 
@@ -107,10 +1649,13 @@ where
     F: FnOnce(i32, i32) -> bool,
 {
     if args.len() != 2 {
-        return err_string(format!(
-            "wrong arity ({}) for comparison between 2 integers",
-            args.len()
-        ));
+        return err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for comparison between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         Integer(left) => match *args[1] {
@@ -119,9 +1664,9 @@ where
             } else {
                 new_false()
             }),
-            _ => err_str("right argument must be an integer"),
+            _ => err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => err_str("left argument must be an integer"),
+        _ => err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 fn lt(args: Vec<MalValue>) -> MalResult {
@@ -148,20 +1693,159 @@ macro_rules! core_function {
     };
 }
 
+/// The capability groups of the core functions, an embedder binding only
+/// the ones it allows (see 'namespace::new_sandbox').
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Functions without side effects.
+    Pure,
+    /// Reading files (including the namespaces files loaded by 'require').
+    IoRead,
+    /// Writing to the standard output.
+    IoWrite,
+    /// Observing or managing the state of the interpreter process shared by
+    /// all its evaluations (e.g. its memory).
+    Process,
+}
+
+/// All the capability groups.
+pub const CAPABILITIES: [Capability; 4] = [
+    Capability::Pure,
+    Capability::IoRead,
+    Capability::IoWrite,
+    Capability::Process,
+];
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Capability::Pure => "pure",
+            Capability::IoRead => "io-read",
+            Capability::IoWrite => "io-write",
+            Capability::Process => "process",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Create and return the (symbol/function) 'HashMap' defining the core MAL
 /// environment.
 /// NB: if the arity indication is not stricly respected, may led to failed
 /// assertions or panics in some functions (e.g. in "list?").
 pub fn ns() -> HashMap<String, MalValue> {
+    ns_with(&CAPABILITIES)
+}
+
+/// Create and return the 'HashMap' of the core functions of the given
+/// capability groups only (see 'ns').
+pub fn ns_with(capabilities: &[Capability]) -> HashMap<String, MalValue> {
+    let mut ns = HashMap::new();
+    for capability in capabilities {
+        ns.extend(match *capability {
+            Capability::Pure => pure_ns(),
+            Capability::IoRead => io_read_ns(),
+            Capability::IoWrite => io_write_ns(),
+            Capability::Process => process_ns(),
+        });
+    }
+    ns
+}
+
+/// Return the core functions without side effects.
+fn pure_ns() -> HashMap<String, MalValue> {
     let mut ns = HashMap::new();
 
     core_function!(ns, "=", eq_q, Some(2));
+    core_function!(ns, "throw", throw, Some(1));
     // list operations
     core_function!(ns, "list", list, None);
     core_function!(ns, "list?", list_q, Some(1));
     // sequence operations
     core_function!(ns, "empty?", empty_q, Some(1));
     core_function!(ns, "count", count, Some(1));
+    core_function!(ns, "seq", seq, Some(1));
+    core_function!(ns, "first", first, Some(1));
+    core_function!(ns, "rest", rest, Some(1));
+    core_function!(ns, "next", next, Some(1));
+    core_function!(ns, "cons", cons, Some(2));
+    core_function!(ns, "conj", conj, None);
+    // lazy sequences
+    core_function!(ns, "iterate", iterate, Some(2));
+    core_function!(ns, "range", range, None);
+    core_function!(ns, "repeat", repeat, None);
+    core_function!(ns, "take", take, Some(2));
+    core_function!(ns, "drop", drop, Some(2));
+    core_function!(ns, "take-while", take_while, Some(2));
+    core_function!(ns, "cycle", cycle, Some(1));
+    // higher-order collection operations
+    core_function!(ns, "map", map, None);
+    core_function!(ns, "filter", filter, Some(2));
+    core_function!(ns, "remove", remove, Some(2));
+    core_function!(ns, "mapcat", mapcat, None);
+    core_function!(ns, "reduce", reduce, None);
+    core_function!(ns, "compare", compare, Some(2));
+    core_function!(ns, "sort", sort, None);
+    core_function!(ns, "sort-by", sort_by, None);
+    core_function!(ns, "group-by", group_by, Some(2));
+    core_function!(ns, "frequencies", frequencies, Some(1));
+    core_function!(ns, "partition", partition, None);
+    core_function!(ns, "interleave", interleave, None);
+    core_function!(ns, "distinct", distinct, Some(1));
+    core_function!(ns, "some", some, Some(2));
+    core_function!(ns, "every?", every_q, Some(2));
+    core_function!(ns, "into", into, Some(2));
+    core_function!(ns, "zipmap", zipmap, Some(2));
+    core_function!(ns, "reverse", reverse, Some(1));
+    core_function!(ns, "last", last, Some(1));
+    core_function!(ns, "butlast", butlast, Some(1));
+    // associative operations
+    core_function!(ns, "assoc", assoc, None);
+    core_function!(ns, "dissoc", dissoc, None);
+    core_function!(ns, "get", get, Some(2));
+    core_function!(ns, "contains?", contains_q, Some(2));
+    // set operations
+    core_function!(ns, "hash-set", hash_set, None);
+    core_function!(ns, "set", set, Some(1));
+    core_function!(ns, "set?", set_q, Some(1));
+    core_function!(ns, "disj", disj, None);
+    core_function!(ns, "union", union, None);
+    core_function!(ns, "intersection", intersection, None);
+    core_function!(ns, "difference", difference, None);
+    core_function!(ns, "subset?", subset_q, Some(2));
+    // string operations
+    core_function!(ns, "str", str, None);
+    core_function!(ns, "pr-str", pr_str, None);
+    core_function!(ns, "subs", subs, None);
+    core_function!(ns, "split", split, Some(2));
+    core_function!(ns, "join", join, None);
+    core_function!(ns, "upper-case", upper_case, Some(1));
+    core_function!(ns, "lower-case", lower_case, Some(1));
+    core_function!(ns, "trim", trim, Some(1));
+    core_function!(ns, "starts-with?", starts_with_q, Some(2));
+    core_function!(ns, "ends-with?", ends_with_q, Some(2));
+    core_function!(ns, "includes?", includes_q, Some(2));
+    core_function!(ns, "index-of", index_of, Some(2));
+    core_function!(ns, "replace", replace, Some(3));
+    core_function!(ns, "char-at", char_at, Some(2));
+    core_function!(ns, "string->list", string_to_list, Some(1));
+    core_function!(ns, "format", format, None);
+    // character operations
+    core_function!(ns, "char", char, Some(1));
+    core_function!(ns, "char?", char_q, Some(1));
+    core_function!(ns, "int", int, Some(1));
+    // regex operations
+    core_function!(ns, "re-pattern", re_pattern, Some(1));
+    core_function!(ns, "re-find", re_find, Some(2));
+    core_function!(ns, "re-matches", re_matches, Some(2));
+    core_function!(ns, "re-seq", re_seq, Some(2));
+    core_function!(ns, "re-replace", re_replace, Some(3));
+    // function metadata
+    core_function!(ns, "doc", doc, Some(1));
+    core_function!(ns, "arglists", arglists, Some(1));
+    core_function!(ns, "source", source, Some(1));
+    // EDN operations
+    core_function!(ns, "edn-read-string", edn_read_string, Some(1));
+    core_function!(ns, "edn-str", edn_str, Some(1));
 
     // integer operations
     core_function!(ns, "+", add, Some(2));
@@ -176,3 +1860,32 @@ pub fn ns() -> HashMap<String, MalValue> {
 
     ns
 }
+
+/// Return the core functions reading files.
+fn io_read_ns() -> HashMap<String, MalValue> {
+    let mut ns = HashMap::new();
+
+    core_function!(ns, "slurp", slurp, Some(1));
+
+    ns
+}
+
+/// Return the core functions writing to the standard output.
+fn io_write_ns() -> HashMap<String, MalValue> {
+    let mut ns = HashMap::new();
+
+    core_function!(ns, "prn", prn, None);
+    core_function!(ns, "println", println, None);
+
+    ns
+}
+
+/// Return the core functions managing the interpreter process.
+fn process_ns() -> HashMap<String, MalValue> {
+    let mut ns = HashMap::new();
+
+    core_function!(ns, "gc", gc, Some(0));
+    core_function!(ns, "memory-stats", memory_stats, Some(0));
+
+    ns
+}
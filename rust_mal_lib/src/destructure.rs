@@ -0,0 +1,380 @@
+/// Module destructuring values along the binding patterns of the let* and
+/// loop bindings and of the functions parameters:
+///
+/// - a symbol binds the whole value,
+/// - a sequential pattern `[a b & rest :as all]` binds the items of a
+///   seqable value (nil when missing), the sequence of the other items and
+///   the whole value,
+/// - a map pattern `{a :a :keys [x y] :strs [s] :or {y 0} :as m}` binds the
+///   values of a hash map (or of a sequence of key/value pairs) by key, the
+///   default values of ':or' being evaluated for the missing keys.
+///
+/// The patterns nest (e.g. `[a {:keys [b]} [c d]]`). They are parsed first,
+/// so that a malformed one is reported whatever the value.
+use super::env::{self, Env};
+use super::seq;
+use super::symbol::{self, SymbolId};
+use super::types::MalType::*;
+use super::types::{
+    new_keyword, new_list, new_nil, new_str, Arity, MalError, MalErrorKind, MalHashContainer,
+    MalHashKey, MalList, MalResult, MalValue,
+};
+
+/// A parsed binding pattern.
+pub enum Pattern {
+    Symbol(SymbolId),
+    Seq(SeqPattern),
+    Map(MapPattern),
+}
+
+/// A sequential pattern, `[items... & rest :as whole]`.
+pub struct SeqPattern {
+    form: MalValue,
+    items: Vec<Pattern>,
+    rest: Option<Box<Pattern>>,
+    whole: Option<SymbolId>,
+}
+
+/// A map pattern, binding each pattern to the value of its key.
+pub struct MapPattern {
+    form: MalValue,
+    entries: Vec<(Pattern, MalValue)>,
+    /// The default values (unevaluated) of the symbols of the entries.
+    defaults: Vec<(SymbolId, MalValue)>,
+    whole: Option<SymbolId>,
+}
+
+fn invalid(pattern: &MalValue, reason: &str) -> MalError {
+    MalError::new(
+        MalErrorKind::Syntax,
+        if reason.is_empty() {
+            format!("invalid binding pattern {}", pattern.pr_str(true))
+        } else {
+            format!("{} in the binding pattern {}", reason, pattern.pr_str(true))
+        },
+    )
+}
+
+fn cannot_destructure(value: &MalValue, pattern: &MalValue) -> MalError {
+    MalError::new(
+        MalErrorKind::Type,
+        format!(
+            "cannot destructure {} with the binding pattern {}",
+            value.pr_str(true),
+            pattern.pr_str(true)
+        ),
+    )
+}
+
+fn is_keyword(value: &MalValue, name: &str) -> bool {
+    matches!(**value, Keyword(ref keyword) if keyword == name)
+}
+
+/// Parse the given binding pattern.
+pub fn parse(form: &MalValue) -> Result<Pattern, MalError> {
+    match **form {
+        Symbol(name) if name != symbol::AMPERSAND => Ok(Pattern::Symbol(name)),
+        Vector(ref items) => Ok(Pattern::Seq(parse_seq(form, items)?)),
+        Hash(ref hash) => parse_map(form, hash),
+        _ => Err(invalid(form, "")),
+    }
+}
+
+fn parse_seq(form: &MalValue, forms: &MalList) -> Result<SeqPattern, MalError> {
+    let mut pattern = SeqPattern {
+        form: form.clone(),
+        items: vec![],
+        rest: None,
+        whole: None,
+    };
+    let mut it = forms.iter();
+    while let Some(item) = it.next() {
+        if is_keyword(item, "as") {
+            match it.next().map(|name| &**name) {
+                Some(&Symbol(name)) if pattern.whole.is_none() => pattern.whole = Some(name),
+                _ => return Err(invalid(form, "missing a symbol after :as")),
+            }
+        } else if pattern.whole.is_some() {
+            return Err(invalid(form, "unexpected pattern after :as"));
+        } else if pattern.rest.is_some() {
+            return Err(invalid(form, "only one pattern allowed after '&'"));
+        } else if let Symbol(symbol::AMPERSAND) = **item {
+            match it.next() {
+                Some(rest) if !is_keyword(rest, "as") => {
+                    pattern.rest = Some(Box::new(parse(rest)?))
+                }
+                _ => return Err(invalid(form, "missing a pattern after '&'")),
+            }
+        } else {
+            pattern.items.push(parse(item)?);
+        }
+    }
+    Ok(pattern)
+}
+
+fn parse_map(form: &MalValue, hash: &MalHashContainer) -> Result<Pattern, MalError> {
+    let mut pattern = MapPattern {
+        form: form.clone(),
+        entries: vec![],
+        defaults: vec![],
+        whole: None,
+    };
+    for (key, value) in hash {
+        let key = key.value();
+        if is_keyword(key, "keys") || is_keyword(key, "strs") {
+            let names = match **value {
+                Vector(ref names) => names,
+                _ => {
+                    return Err(invalid(
+                        form,
+                        "missing a vector of symbols after :keys or :strs",
+                    ))
+                }
+            };
+            for name in names {
+                let name = match **name {
+                    Symbol(name) => name,
+                    _ => {
+                        return Err(invalid(
+                            form,
+                            "missing a vector of symbols after :keys or :strs",
+                        ))
+                    }
+                };
+                let lookup_key = if is_keyword(key, "keys") {
+                    new_keyword(name.name().to_string())
+                } else {
+                    new_str(name.name().to_string())
+                };
+                pattern.entries.push((Pattern::Symbol(name), lookup_key));
+            }
+        } else if is_keyword(key, "or") {
+            let defaults = match **value {
+                Hash(ref defaults) => defaults,
+                _ => return Err(invalid(form, "missing a map of symbols after :or")),
+            };
+            for (name, default) in defaults {
+                match **name.value() {
+                    Symbol(name) => pattern.defaults.push((name, default.clone())),
+                    _ => return Err(invalid(form, "missing a map of symbols after :or")),
+                }
+            }
+        } else if is_keyword(key, "as") {
+            match **value {
+                Symbol(name) => pattern.whole = Some(name),
+                _ => return Err(invalid(form, "missing a symbol after :as")),
+            }
+        } else {
+            pattern.entries.push((parse(key)?, value.clone()));
+        }
+    }
+    Ok(Pattern::Map(pattern))
+}
+
+/// Parse the given parameters list of a function: the patterns of the
+/// required parameters, then the one of the other arguments after '&' (if
+/// variadic), each symbol being bound once.
+pub fn parse_params(params: &MalValue) -> Result<SeqPattern, MalError> {
+    let pattern = match **params {
+        List(ref forms, _) | Vector(ref forms) => parse_seq(params, forms)?,
+        _ => return Err(invalid(params, "")),
+    };
+    if pattern.whole.is_some() {
+        return Err(invalid(params, "unexpected :as"));
+    }
+    let mut symbols = vec![];
+    pattern.collect_symbols(&mut symbols);
+    for (i, name) in symbols.iter().enumerate() {
+        if symbols[..i].contains(name) {
+            return Err(invalid(params, &format!("duplicate symbol {}", name)));
+        }
+    }
+    Ok(pattern)
+}
+
+impl Pattern {
+    /// Return the symbols bound by the pattern.
+    pub fn symbols(&self) -> Vec<SymbolId> {
+        let mut symbols = vec![];
+        self.collect_symbols(&mut symbols);
+        symbols
+    }
+
+    fn collect_symbols(&self, symbols: &mut Vec<SymbolId>) {
+        match *self {
+            Pattern::Symbol(name) => symbols.push(name),
+            Pattern::Seq(ref pattern) => pattern.collect_symbols(symbols),
+            Pattern::Map(ref pattern) => {
+                symbols.extend(pattern.whole);
+                for (entry, _) in &pattern.entries {
+                    entry.collect_symbols(symbols);
+                }
+            }
+        }
+    }
+
+    /// Return the (unevaluated) default values of the pattern.
+    pub fn defaults(&self) -> Vec<MalValue> {
+        match *self {
+            Pattern::Symbol(_) => vec![],
+            Pattern::Seq(ref pattern) => pattern
+                .items
+                .iter()
+                .chain(pattern.rest.as_deref())
+                .flat_map(Pattern::defaults)
+                .collect(),
+            Pattern::Map(ref pattern) => pattern
+                .defaults
+                .iter()
+                .map(|(_, form)| form.clone())
+                .chain(
+                    pattern
+                        .entries
+                        .iter()
+                        .flat_map(|(entry, _)| entry.defaults()),
+                )
+                .collect(),
+        }
+    }
+
+    /// Destructure the given value: call bind with each symbol of the
+    /// pattern and its value, default evaluating the default values of the
+    /// missing keys.
+    pub fn bind(
+        &self,
+        value: MalValue,
+        bind: &mut dyn FnMut(SymbolId, MalValue),
+        default: &mut dyn FnMut(&MalValue) -> MalResult,
+    ) -> Result<(), MalError> {
+        match *self {
+            Pattern::Symbol(name) => {
+                bind(name, value);
+                Ok(())
+            }
+            Pattern::Seq(ref pattern) => pattern.bind(value, bind, default),
+            Pattern::Map(ref pattern) => pattern.bind(value, bind, default),
+        }
+    }
+
+    /// Destructure the given value in the given environment, the default
+    /// values being evaluated in it with the given evaluator.
+    pub fn bind_env(
+        &self,
+        env: &Env,
+        value: MalValue,
+        eval: fn(MalValue, Env) -> MalResult,
+    ) -> Result<(), MalError> {
+        self.bind(
+            value,
+            &mut |name, value| env::define(env, name, value),
+            &mut |form| eval(form.clone(), env.clone()),
+        )
+    }
+}
+
+impl SeqPattern {
+    fn collect_symbols(&self, symbols: &mut Vec<SymbolId>) {
+        symbols.extend(self.whole);
+        for item in &self.items {
+            item.collect_symbols(symbols);
+        }
+        if let Some(ref rest) = self.rest {
+            rest.collect_symbols(symbols);
+        }
+    }
+
+    fn bind(
+        &self,
+        value: MalValue,
+        bind: &mut dyn FnMut(SymbolId, MalValue),
+        default: &mut dyn FnMut(&MalValue) -> MalResult,
+    ) -> Result<(), MalError> {
+        match *value {
+            Nil | List(..) | Vector(_) | Hash(_) | Set(_) | Str(_) | LazySeq(_) => (),
+            _ => return Err(cannot_destructure(&value, &self.form)),
+        }
+        if let Some(whole) = self.whole {
+            bind(whole, value.clone());
+        }
+        let mut remaining = value;
+        for item in &self.items {
+            match seq::first_rest(&remaining)? {
+                Some((first, rest)) => {
+                    item.bind(first, bind, default)?;
+                    remaining = rest;
+                }
+                None => item.bind(new_nil(), bind, default)?,
+            }
+        }
+        if let Some(ref rest) = self.rest {
+            let rest_value = match *seq::seq(&remaining)? {
+                Nil => new_list(vec![]),
+                _ => seq::seq(&remaining)?,
+            };
+            rest.bind(rest_value, bind, default)?;
+        }
+        Ok(())
+    }
+
+    /// Bind the given arguments of a function to the parameters.
+    pub fn bind_args(
+        &self,
+        mut args: Vec<MalValue>,
+        bind: &mut dyn FnMut(SymbolId, MalValue),
+        default: &mut dyn FnMut(&MalValue) -> MalResult,
+    ) -> Result<(), MalError> {
+        Arity {
+            required: self.items.len(),
+            variadic: self.rest.is_some(),
+        }
+        .check(args.len())?;
+        let rest = args.split_off(self.items.len());
+        for (item, arg) in self.items.iter().zip(args) {
+            item.bind(arg, bind, default)?;
+        }
+        if let Some(ref pattern) = self.rest {
+            pattern.bind(new_list(rest), bind, default)?;
+        }
+        Ok(())
+    }
+}
+
+impl MapPattern {
+    fn bind(
+        &self,
+        value: MalValue,
+        bind: &mut dyn FnMut(SymbolId, MalValue),
+        default: &mut dyn FnMut(&MalValue) -> MalResult,
+    ) -> Result<(), MalError> {
+        let hash = match *value {
+            Hash(ref hash) => hash.clone(),
+            Nil => MalHashContainer::new(),
+            List(ref items, _) | Vector(ref items) if items.len() % 2 == 0 => {
+                let mut hash = MalHashContainer::new();
+                let mut it = items.iter();
+                while let (Some(key), Some(value)) = (it.next(), it.next()) {
+                    hash.insert(MalHashKey::new(key.clone()), value.clone());
+                }
+                hash
+            }
+            _ => return Err(cannot_destructure(&value, &self.form)),
+        };
+        if let Some(whole) = self.whole {
+            bind(whole, value.clone());
+        }
+        for (entry, key) in &self.entries {
+            let entry_value = match hash.get(&MalHashKey::new(key.clone())) {
+                Some(entry_value) => entry_value.clone(),
+                None => match *entry {
+                    Pattern::Symbol(name) => match self.defaults.iter().find(|d| d.0 == name) {
+                        Some((_, form)) => default(form)?,
+                        None => new_nil(),
+                    },
+                    _ => new_nil(),
+                },
+            };
+            entry.bind(entry_value, bind, default)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,466 @@
+/// Module reading and writing MAL values as EDN (extensible data notation),
+/// for data interchange with Clojure tooling.
+///
+/// Unlike the MAL reader, EDN is pure data: there is no evaluation and no
+/// reader macro (quote, deref, metadata...), but it supports sets, characters,
+/// discarded forms and tagged literals (`#inst`, `#uuid` are validated, other
+/// tags are kept as is).
+use regex::Regex;
+
+use super::seq;
+use super::types;
+use super::types::MalType::*;
+use super::types::{
+    err_str, err_string, MalError, MalErrorKind, MalHashContainer, MalHashKey, MalResult,
+    MalSetContainer, MalValue,
+};
+
+const MATCH_INST_PCRE: &str = concat!(
+    r#"^\d{4}(-\d{2}(-\d{2}(T\d{2}:\d{2}(:\d{2}(\.\d+)?)?"#,
+    r#"(Z|[+-]\d{2}:\d{2})?)?)?)?$"#
+);
+const MATCH_UUID_PCRE: &str = r#"^[0-9a-fA-F]{8}(-[0-9a-fA-F]{4}){3}-[0-9a-fA-F]{12}$"#;
+
+/// Read the single EDN value in the given string.
+///
+/// An empty input (or one containing only whitespace, comments and discarded
+/// forms) reads as nil.
+pub fn read_edn(string: &str) -> MalResult {
+    let mut reader = EdnReader {
+        chars: string.chars().collect(),
+        position: 0,
+    };
+    let value = match reader.read_next()? {
+        Some(value) => value,
+        None => return Ok(types::new_nil()),
+    };
+    match reader.read_next()? {
+        None => Ok(value),
+        Some(_) => err_str(
+            MalErrorKind::Reader,
+            "edn: unexpected data after the first value",
+        ),
+    }
+}
+
+/// Write the given value as an EDN string.
+///
+/// Fails on values with no EDN representation (i.e. functions).
+pub fn write_edn(value: &MalValue) -> Result<String, MalError> {
+    let mut output = String::new();
+    write_value(value, &mut output)?;
+    Ok(output)
+}
+
+struct EdnReader {
+    chars: Vec<char>,
+    position: usize,
+}
+
+/// Return true if the given character ends a token.
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == ',' || "()[]{}\";".contains(c)
+}
+
+impl EdnReader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    /// Skip the whitespace (including commas) and the comments.
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ';' {
+                while let Some(c) = self.next() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if c.is_whitespace() || c == ',' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consume the characters up to the next delimiter.
+    fn read_token(&mut self) -> String {
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if is_delimiter(c) {
+                break;
+            }
+            token.push(c);
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Read the next value, or return None at the end of the input.
+    fn read_next(&mut self) -> Result<Option<MalValue>, MalError> {
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Ok(None),
+                Some('#') if self.chars.get(self.position + 1) == Some(&'_') => {
+                    self.position += 2;
+                    if self.read_next()?.is_none() {
+                        return Err(MalError::new(
+                            MalErrorKind::Reader,
+                            "edn: expected a form to discard after '#_', got EOF",
+                        ));
+                    }
+                }
+                Some(_) => return self.read_form().map(Some),
+            }
+        }
+    }
+
+    /// Read the next value, failing at the end of the input.
+    fn read_required(&mut self, context: &str) -> MalResult {
+        match self.read_next()? {
+            Some(value) => Ok(value),
+            None => err_string(
+                MalErrorKind::Reader,
+                format!("edn: expected a value {}, got EOF", context),
+            ),
+        }
+    }
+
+    fn read_form(&mut self) -> MalResult {
+        match self.peek().unwrap() {
+            '(' => self.read_seq('(', ')').map(types::new_list),
+            '[' => self.read_seq('[', ']').map(types::new_vector),
+            '{' => self.read_map(),
+            ')' | ']' | '}' => err_string(
+                MalErrorKind::Reader,
+                format!("edn: unexpected '{}'", self.next().unwrap()),
+            ),
+            '"' => self.read_string(),
+            '\\' => self.read_char(),
+            '#' => self.read_dispatch(),
+            '\'' | '`' | '~' | '@' | '^' => err_string(
+                MalErrorKind::Reader,
+                format!(
+                    "edn: reader macro '{}' is not allowed",
+                    self.next().unwrap()
+                ),
+            ),
+            _ => self.read_atom(),
+        }
+    }
+
+    /// Read the sequence of values between the given delimiters.
+    fn read_seq(&mut self, start: char, end: char) -> Result<Vec<MalValue>, MalError> {
+        debug_assert_eq!(self.peek(), Some(start));
+        self.position += 1;
+        let mut seq = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => {
+                    return Err(MalError::new(
+                        MalErrorKind::Reader,
+                        format!("edn: expected '{}', got EOF", end),
+                    ))
+                }
+                Some(c) if c == end => {
+                    self.position += 1;
+                    return Ok(seq);
+                }
+                Some('#') if self.chars.get(self.position + 1) == Some(&'_') => {
+                    self.position += 2;
+                    self.read_required("to discard after '#_'")?;
+                }
+                Some(_) => seq.push(self.read_form()?),
+            }
+        }
+    }
+
+    fn read_map(&mut self) -> MalResult {
+        let seq = self.read_seq('{', '}')?;
+        if seq.len() % 2 != 0 {
+            return err_str(
+                MalErrorKind::Reader,
+                "edn: unbalanced map (key with no value)",
+            );
+        }
+        let mut map = MalHashContainer::new();
+        for entry in seq.chunks(2) {
+            let key = MalHashKey::new(entry[0].clone());
+            if map.insert(key, entry[1].clone()).is_some() {
+                return err_string(
+                    MalErrorKind::Reader,
+                    format!("edn: duplicate map key {}", entry[0].pr_str(true)),
+                );
+            }
+        }
+        Ok(types::new_hash(map))
+    }
+
+    fn read_set(&mut self) -> MalResult {
+        let seq = self.read_seq('{', '}')?;
+        let mut set = MalSetContainer::new();
+        for value in seq {
+            if set.insert(MalHashKey::new(value.clone())).is_some() {
+                return err_string(
+                    MalErrorKind::Reader,
+                    format!("edn: duplicate set element {}", value.pr_str(true)),
+                );
+            }
+        }
+        Ok(types::new_set(set))
+    }
+
+    fn read_string(&mut self) -> MalResult {
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None => return err_str(MalErrorKind::Reader, "edn: expected '\"', got EOF"),
+                Some('"') => return Ok(types::new_str(string)),
+                Some('\\') => string.push(match self.next() {
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('n') => '\n',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.next()).collect();
+                        parse_unicode_escape(&code)?
+                    }
+                    Some(c) => {
+                        return err_string(
+                            MalErrorKind::Reader,
+                            format!("edn: unsupported escape '\\{}'", c),
+                        )
+                    }
+                    None => return err_str(MalErrorKind::Reader, "edn: expected '\"', got EOF"),
+                }),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn read_char(&mut self) -> MalResult {
+        self.position += 1;
+        // the first character is always part of the literal (e.g. "\(")
+        let first = match self.next() {
+            Some(c) => c,
+            None => {
+                return err_str(
+                    MalErrorKind::Reader,
+                    "edn: expected a character after '\\', got EOF",
+                )
+            }
+        };
+        let token = format!("{}{}", first, self.read_token());
+        let c = match &token[..] {
+            "newline" => '\n',
+            "return" => '\r',
+            "space" => ' ',
+            "tab" => '\t',
+            _ if token.chars().count() == 1 => first,
+            _ if token.starts_with('u') && token.len() == 5 => parse_unicode_escape(&token[1..])?,
+            _ => {
+                return err_string(
+                    MalErrorKind::Reader,
+                    format!("edn: invalid character literal '\\{}'", token),
+                )
+            }
+        };
+        Ok(types::new_char(c))
+    }
+
+    /// Read a form starting with '#' (set or tagged literal).
+    fn read_dispatch(&mut self) -> MalResult {
+        self.position += 1;
+        match self.peek() {
+            Some('{') => self.read_set(),
+            Some(c) if c.is_alphabetic() => {
+                let tag = self.read_token();
+                let value = self.read_required(&format!("for the tag #{}", tag))?;
+                read_tagged(tag, value)
+            }
+            Some('#') => err_str(
+                MalErrorKind::Reader,
+                "edn: symbolic floating point values are not supported",
+            ),
+            Some(c) => err_string(
+                MalErrorKind::Reader,
+                format!("edn: invalid dispatch '#{}'", c),
+            ),
+            None => err_str(
+                MalErrorKind::Reader,
+                "edn: expected a form after '#', got EOF",
+            ),
+        }
+    }
+
+    fn read_atom(&mut self) -> MalResult {
+        let token = self.read_token();
+        match &token[..] {
+            "nil" => return Ok(types::new_nil()),
+            "true" => return Ok(types::new_true()),
+            "false" => return Ok(types::new_false()),
+            _ => (),
+        }
+        let mut chars = token.chars();
+        let first = chars.next().unwrap();
+        let second = chars.next();
+        if first.is_ascii_digit()
+            || ((first == '+' || first == '-') && second.is_some_and(|c| c.is_ascii_digit()))
+        {
+            read_number(&token)
+        } else if first == ':' {
+            if second.is_none() || second == Some(':') || token.ends_with('/') {
+                err_string(
+                    MalErrorKind::Reader,
+                    format!("edn: invalid keyword '{}'", token),
+                )
+            } else {
+                Ok(types::new_keyword(token[1..].to_string()))
+            }
+        } else if token.ends_with('/') && token != "/" {
+            err_string(
+                MalErrorKind::Reader,
+                format!("edn: invalid symbol '{}'", token),
+            )
+        } else {
+            Ok(types::new_symbol(&token))
+        }
+    }
+}
+
+fn parse_unicode_escape(code: &str) -> Result<char, MalError> {
+    u32::from_str_radix(code, 16)
+        .ok()
+        .filter(|_| code.len() == 4)
+        .and_then(std::char::from_u32)
+        .ok_or_else(|| {
+            MalError::new(
+                MalErrorKind::Reader,
+                format!("edn: invalid unicode escape '\\u{}'", code),
+            )
+        })
+}
+
+fn read_number(token: &str) -> MalResult {
+    let digits = token.strip_suffix('N').unwrap_or(token);
+    match digits.parse::<i32>() {
+        Ok(integer) => Ok(types::new_integer(integer)),
+        Err(_) if token.contains(['.', 'e', 'E', 'M']) => err_string(
+            MalErrorKind::Reader,
+            format!("edn: floating point number '{}' is not supported", token),
+        ),
+        Err(_) if digits[1..].chars().all(|c| c.is_ascii_digit()) => err_string(
+            MalErrorKind::Reader,
+            format!("edn: integer '{}' is out of range", token),
+        ),
+        Err(_) => err_string(
+            MalErrorKind::Reader,
+            format!("edn: invalid number '{}'", token),
+        ),
+    }
+}
+
+/// Validate the built-in tagged literals and build the tagged value.
+fn read_tagged(tag: String, value: MalValue) -> MalResult {
+    let (pattern, expected) = match &tag[..] {
+        "inst" => (MATCH_INST_PCRE, "an RFC 3339 timestamp"),
+        "uuid" => (MATCH_UUID_PCRE, "a UUID"),
+        _ => return Ok(types::new_tagged(tag, value)),
+    };
+    match *value {
+        Str(ref string) if mal_regex!(pattern).is_match(string) => {
+            Ok(types::new_tagged(tag, value.clone()))
+        }
+        _ => err_string(
+            MalErrorKind::Reader,
+            format!(
+                "edn: #{} expects a string containing {}, got {}",
+                tag,
+                expected,
+                value.pr_str(true)
+            ),
+        ),
+    }
+}
+
+fn write_seq<'a, I>(seq: I, start: &str, end: &str, output: &mut String) -> Result<(), MalError>
+where
+    I: Iterator<Item = &'a MalValue>,
+{
+    output.push_str(start);
+    for (i, value) in seq.enumerate() {
+        if i > 0 {
+            output.push(' ');
+        }
+        write_value(value, output)?;
+    }
+    output.push_str(end);
+    Ok(())
+}
+
+fn write_value(value: &MalValue, output: &mut String) -> Result<(), MalError> {
+    match **value {
+        Nil | True | False | Integer(_) | Char(_) | Keyword(_) => {
+            output.push_str(&value.pr_str(true))
+        }
+        // a symbol resolved to a local binding (see 'resolve') is written as
+        // the plain symbol, the resolution being internal to the evaluation
+        Symbol(symbol) | LocalSymbol(symbol, _) => output.push_str(&symbol.name()),
+        Str(ref string) => {
+            output.push('"');
+            for c in string.chars() {
+                match c {
+                    '"' => output.push_str("\\\""),
+                    '\\' => output.push_str("\\\\"),
+                    '\n' => output.push_str("\\n"),
+                    '\r' => output.push_str("\\r"),
+                    '\t' => output.push_str("\\t"),
+                    _ => output.push(c),
+                }
+            }
+            output.push('"');
+        }
+        List(ref seq, _) => write_seq(seq.iter(), "(", ")", output)?,
+        LazySeq(_) => write_seq(seq::to_vec(value)?.iter(), "(", ")", output)?,
+        Vector(ref seq) => write_seq(seq.iter(), "[", "]", output)?,
+        Hash(ref hash) => write_seq(
+            hash.iter().flat_map(|(k, v)| vec![k.value(), v]),
+            "{",
+            "}",
+            output,
+        )?,
+        Set(ref set) => write_seq(set.iter().map(|k| k.value()), "#{", "}", output)?,
+        Tagged(ref tag, ref tagged) => {
+            output.push('#');
+            output.push_str(tag);
+            output.push(' ');
+            write_value(tagged, output)?;
+        }
+        Function(_) | MalFunction(_) => {
+            return Err(MalError::new(
+                MalErrorKind::Type,
+                format!("edn: cannot write the function {}", value.pr_str(true)),
+            ))
+        }
+        Regex(_) => {
+            return Err(MalError::new(
+                MalErrorKind::Type,
+                format!("edn: cannot write the regex {}", value.pr_str(true)),
+            ))
+        }
+    }
+    Ok(())
+}
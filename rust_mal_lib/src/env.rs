@@ -1,14 +1,22 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 
+use super::destructure;
+use super::gc;
+use super::namespace::Namespace;
+use super::symbol::{self, SymbolId, SymbolMap};
 use super::types;
 use super::types::MalType::{List, Symbol, Vector};
-use super::types::{new_list, MalResult, MalValue};
+use super::types::{new_list, Arity, MalError, MalErrorKind, MalList, MalResult, MalValue};
 
 pub struct EnvData {
-    data: HashMap<String, MalValue>,
+    data: SymbolMap<MalValue>,
+    /// The values of the local bindings resolved to slots (see 'analyzer'),
+    /// none for the let* bindings not evaluated yet.
+    slots: Vec<Option<MalValue>>,
     outer: Option<Env>,
+    /// The namespace, if this is the root environment of one.
+    namespace: Option<Rc<Namespace>>,
 }
 
 /// Main Trait to interact with a Make A Lisp Environment.
@@ -21,7 +29,8 @@ pub trait Environment: Clone + Sized {
 
     /// Return the root outer of this environment (can be itself if no outer).
     ///
-    /// TODO: find way to return reference without "reference to temporary value" error
+    /// The environments being shared handles, only the returned one is cloned
+    /// (not the ones walked through).
     fn root(&self) -> Self;
 
     /// Try to found, if it exists, the value with the associated key (must be a
@@ -31,17 +40,33 @@ pub trait Environment: Clone + Sized {
     /// Associate the given key (must be a Symbol) with the given MAL value in the
     /// current environment.
     fn set_env_value(&mut self, key: MalValue, val: MalValue) -> &mut Self;
+
+    /// Remove, if it exists, the value associated with the given key (must be
+    /// a Symbol) in the current environment only, and return it.
+    fn remove_env_value(&mut self, key: &MalValue) -> Option<MalValue>;
+
+    /// Return the outer environment, if any.
+    fn outer(&self) -> Option<Self>;
+
+    /// Return the (symbol, value) pairs bound in the current environment only
+    /// (not in its outers), sorted by symbol name.
+    fn bindings(&self) -> Vec<(MalValue, MalValue)>;
 }
 
 /// Handler for an 'EnvData' instance.
 pub type Env = Rc<RefCell<EnvData>>;
 
+/// Create a new 'Env' instance with the given data, tracked by the cycle
+/// collector (see 'gc').
+fn create(data: EnvData) -> Env {
+    let env = Rc::new(RefCell::new(data));
+    gc::track(&env);
+    env
+}
+
 impl Environment for Env {
     fn new(outer: Option<&Self>) -> Self {
-        Rc::new(RefCell::new(EnvData {
-            data: HashMap::new(),
-            outer: outer.cloned(),
-        }))
+        self::new(outer.cloned())
     }
 
     fn new_inner(&self) -> Self {
@@ -57,31 +82,85 @@ impl Environment for Env {
 
     fn get_env_value(&self, key: &MalValue) -> MalResult {
         match **key {
-            Symbol(ref symbol) => match find(self, key) {
-                Some(env) => Ok(env.borrow().data.get(symbol).unwrap().clone()),
-                None => types::err_string(format!("env: cannot find {}", symbol)),
+            Symbol(symbol) => match lookup(self, symbol) {
+                Some(value) => Ok(value),
+                None => types::err_string(
+                    MalErrorKind::UnboundSymbol,
+                    format!("env: '{}' not found", symbol),
+                ),
             },
-            _ => types::err_str("env: cannot get with a non-symbol key"),
+            _ => types::err_str(MalErrorKind::Type, "env: cannot get with a non-symbol key"),
         }
     }
 
     fn set_env_value(&mut self, key: MalValue, val: MalValue) -> &mut Self {
         match *key {
-            Symbol(ref symbol) => {
-                self.borrow_mut().data.insert(symbol.clone(), val);
+            Symbol(symbol) => {
+                self.borrow_mut().data.insert(symbol, val);
             }
             _ => warn!("env: cannot set with a non-symbol key"),
         }
         self
     }
+
+    fn remove_env_value(&mut self, key: &MalValue) -> Option<MalValue> {
+        match **key {
+            Symbol(ref symbol) => self.borrow_mut().data.remove(symbol),
+            _ => None,
+        }
+    }
+
+    fn outer(&self) -> Option<Self> {
+        self.borrow().outer.clone()
+    }
+
+    fn bindings(&self) -> Vec<(MalValue, MalValue)> {
+        let mut bindings = self
+            .borrow()
+            .data
+            .iter()
+            .map(|(&symbol, value)| (Rc::new(Symbol(symbol)), value.clone()))
+            .collect::<Vec<_>>();
+        bindings.sort_by(|(a, _), (b, _)| types::compare_values(a, b));
+        bindings
+    }
+}
+
+/// Return an iterator over the given environment then its outers, from the
+/// innermost to the root one.
+pub fn chain<E: Environment>(env: &E) -> impl Iterator<Item = E> {
+    std::iter::successors(Some(env.clone()), Environment::outer)
 }
 
 /// Create a new 'Env' instance with the (optional) outer environment.
 pub fn new(outer: Option<Env>) -> Env {
-    Rc::new(RefCell::new(EnvData {
-        data: HashMap::new(),
+    create(EnvData {
+        data: SymbolMap::default(),
+        slots: vec![],
         outer,
-    }))
+        namespace: None,
+    })
+}
+
+/// Create a new 'Env' instance for the given namespace.
+pub fn new_namespace(outer: Option<Env>, namespace: Namespace) -> Env {
+    create(EnvData {
+        data: SymbolMap::default(),
+        slots: vec![],
+        outer,
+        namespace: Some(Rc::new(namespace)),
+    })
+}
+
+/// Return the environment of the namespace the given 'Env' belongs to, i.e.
+/// the innermost one of its chain with a namespace, if any.
+pub fn namespace_env(env: &Env) -> Option<Env> {
+    chain(env).find(|env| env.borrow().namespace.is_some())
+}
+
+/// Return the namespace the given 'Env' belongs to, if any.
+pub fn namespace(env: &Env) -> Option<Rc<Namespace>> {
+    namespace_env(env).and_then(|env| env.borrow().namespace.clone())
 }
 
 /// Return the root environment of the given 'Env'.
@@ -92,13 +171,138 @@ pub fn root(env: &Env) -> Env {
     }
 }
 
+/// Return the value bound to the given symbol in the given environment or,
+/// if any, in the first outer environment binding it.
+///
+/// Every environment of the chain is borrowed (not cloned) and hashed once.
+pub fn lookup(env: &Env, symbol: SymbolId) -> Option<MalValue> {
+    let env_data = env.borrow();
+    match env_data.data.get(&symbol) {
+        Some(value) => Some(value.clone()),
+        None => env_data
+            .outer
+            .as_ref()
+            .and_then(|outer| lookup(outer, symbol)),
+    }
+}
+
+/// Bind the given symbol to the given value in the given environment.
+pub fn define(env: &Env, symbol: SymbolId, value: MalValue) {
+    env.borrow_mut().data.insert(symbol, value);
+}
+
+/// Return the value bound to the given symbol in the given environment only
+/// (not in its outers).
+pub fn lookup_local(env: &Env, symbol: SymbolId) -> Option<MalValue> {
+    env.borrow().data.get(&symbol).cloned()
+}
+
+/// Return the value bound to the given symbol at the given lexical address,
+/// i.e. in the depth-th outer of the given environment (with no hashing of
+/// the environments in between), or if it is not bound there in its outers.
+pub fn lookup_at(env: &Env, symbol: SymbolId, depth: usize) -> Option<MalValue> {
+    if depth == 0 {
+        return lookup(env, symbol);
+    }
+    match env.borrow().outer {
+        Some(ref outer) => lookup_at(outer, symbol, depth - 1),
+        None => None,
+    }
+}
+
+/// Create a new 'Env' instance, with the given outer environment and slots.
+pub fn new_frame(outer: &Env, slots: Vec<Option<MalValue>>) -> Env {
+    create(EnvData {
+        data: SymbolMap::default(),
+        slots,
+        outer: Some(outer.clone()),
+        namespace: None,
+    })
+}
+
+/// Call outer with the outer environment of the given environment (if any),
+/// value with each value bound in it (symbols and slots) and namespace with
+/// its namespace (if any), and return true, or return false if it is being
+/// modified.
+pub fn trace(
+    env: &Env,
+    outer: &mut dyn FnMut(&Env),
+    value: &mut dyn FnMut(&MalValue),
+    namespace: &mut dyn FnMut(&Rc<Namespace>),
+) -> bool {
+    let env_data = match env.try_borrow() {
+        Ok(env_data) => env_data,
+        Err(_) => return false,
+    };
+    if let Some(ref outer_env) = env_data.outer {
+        outer(outer_env);
+    }
+    for bound in env_data
+        .data
+        .values()
+        .chain(env_data.slots.iter().flatten())
+    {
+        value(bound);
+    }
+    if let Some(ref env_namespace) = env_data.namespace {
+        namespace(env_namespace);
+    }
+    true
+}
+
+/// Unbind all the symbols and slots of the given environment and detach it
+/// from its namespace (unless it is being used), dropping their values once
+/// it is released.
+pub fn clear(env: &Env) {
+    let cleared = match env.try_borrow_mut() {
+        Ok(mut env_data) => Some((
+            std::mem::take(&mut env_data.data),
+            std::mem::take(&mut env_data.slots),
+            env_data.namespace.take(),
+        )),
+        Err(_) => None,
+    };
+    drop(cleared);
+}
+
+/// Return the value of the given slot of the depth-th outer of the given
+/// environment, if it is bound.
+///
+/// Panics if there is no such slot (the analyzer resolving only the local
+/// bindings to slots).
+pub fn slot(env: &Env, depth: usize, index: usize) -> Option<MalValue> {
+    let env_data = env.borrow();
+    if depth == 0 {
+        return env_data.slots[index].clone();
+    }
+    slot(env_data.outer.as_ref().unwrap(), depth - 1, index)
+}
+
+/// Set the value of the given slot of the given environment.
+pub fn set_slot(env: &Env, index: usize, value: MalValue) {
+    env.borrow_mut().slots[index] = Some(value);
+}
+
+/// Bind the given arguments to the slots of a new environment, with the
+/// given environment as its outer, for a function of the given arity: one
+/// slot per required parameter then, if variadic, one for the list of the
+/// other arguments.
+pub fn bind_slots(outer: &Env, arity: Arity, mut args: Vec<MalValue>) -> Result<Env, MalError> {
+    arity.check(args.len())?;
+    let rest = args.split_off(arity.required);
+    if arity.variadic {
+        args.push(new_list(rest));
+    }
+    Ok(new_frame(outer, args.into_iter().map(Some).collect()))
+}
+
 /// Return the given environment if it contains the given key (must be a Symbol)
 /// or, if any, the first outer environment containing it.
 pub fn find(env: &Env, key: &MalValue) -> Option<Env> {
     match **key {
-        Symbol(ref symbol) => {
+        Symbol(symbol) => {
             let env_data = env.borrow();
-            if env_data.data.contains_key(symbol) {
+            if env_data.data.contains_key(&symbol) {
                 Some(env.clone())
             } else {
                 match env_data.outer {
@@ -116,45 +320,62 @@ pub fn find(env: &Env, key: &MalValue) -> Option<Env> {
 /// The binding will be variadic if a '&' symbol is encountered in the bindings
 /// list ; in this case the next symbol in the bindings list is bound to the
 ///  rest of the exprs.
-pub fn bind(outer: &Env, binds: MalValue, exprs: MalValue) -> Result<Env, String> {
+/// The binds which are not all symbols are destructured (see 'destructure'),
+/// their default values being evaluated in the new environment with eval ;
+/// a malformed bindings list is reported the same way.
+pub fn bind(
+    outer: &Env,
+    binds: MalValue,
+    exprs: MalValue,
+    eval: fn(MalValue, Env) -> MalResult,
+) -> Result<Env, MalError> {
     let mut env = new(Some(outer.clone()));
-    let mut variadic_pos: Option<usize> = None;
     match *binds {
-        List(ref binds_seq) | Vector(ref binds_seq) => match *exprs {
-            List(ref exprs_seq) | Vector(ref exprs_seq) => {
-                for (i, bind) in binds_seq.iter().enumerate() {
-                    match **bind {
-                        Symbol(ref bind_key) => {
-                            if *bind_key == "&" {
-                                variadic_pos = Some(i);
-                                break;
-                            } else if i >= exprs_seq.len() {
-                                return Err("not enough parameters for binding".into());
-                            }
-                            env.set_env_value(bind.clone(), exprs_seq[i].clone());
-                        }
-                        _ => return Err("non-symbol bind".into()),
-                    }
+        List(ref binds_seq, _) | Vector(ref binds_seq) => match *exprs {
+            List(ref exprs_seq, _) | Vector(ref exprs_seq) if is_simple_params(binds_seq) => {
+                let arity = Arity::of_params(&binds);
+                arity.check(exprs_seq.len())?;
+                for (bind, expr) in binds_seq.iter().zip(exprs_seq.iter()).take(arity.required) {
+                    env.set_env_value(bind.clone(), expr.clone());
                 }
-
-                if let Some(i) = variadic_pos {
-                    if i >= binds_seq.len() {
-                        return Err(
-                            concat!("missing a symbol after '&'", " for variadic binding").into(),
-                        );
-                    }
-                    let vbind = &binds_seq[i + 1];
-                    match **vbind {
-                        Symbol(_) => {
-                            env.set_env_value(vbind.clone(), new_list(exprs_seq[i..].to_vec()));
-                        }
-                        _ => return Err("non-symbol variadic binding".into()),
-                    }
+                if arity.variadic {
+                    env.set_env_value(
+                        binds_seq[arity.required + 1].clone(),
+                        new_list(exprs_seq.skip(arity.required)),
+                    );
                 }
                 Ok(env)
             }
-            _ => Err("env: exprs must be a list/vector".into()),
+            List(ref exprs_seq, _) | Vector(ref exprs_seq) => {
+                let params = destructure::parse_params(&binds)?;
+                params.bind_args(
+                    exprs_seq.iter().cloned().collect(),
+                    &mut |name, value| define(&env, name, value),
+                    &mut |form| eval(form.clone(), env.clone()),
+                )?;
+                Ok(env)
+            }
+            _ => Err(MalError::new(
+                MalErrorKind::Type,
+                "env: exprs must be a list/vector",
+            )),
         },
-        _ => Err("env: binds must be a list/vector".into()),
+        _ => Err(MalError::new(
+            MalErrorKind::Type,
+            "env: binds must be a list/vector",
+        )),
     }
 }
+
+/// Return true if the given bindings list only has symbols, with at most one
+/// '&' followed by a single symbol.
+fn is_simple_params(binds: &MalList) -> bool {
+    binds.iter().all(|bind| matches!(**bind, Symbol(_)))
+        && match binds
+            .iter()
+            .position(|bind| matches!(**bind, Symbol(symbol::AMPERSAND)))
+        {
+            Some(i) => i + 2 == binds.len(),
+            None => true,
+        }
+}
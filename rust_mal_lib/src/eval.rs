@@ -0,0 +1,639 @@
+/// Module evaluating an AST of 'MalValue' in an environment: the evaluator
+/// shared by the full interpreter (and any embedder), beyond the step-by-step
+/// ones of the guide.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::destructure::{self, Pattern};
+use super::env::{self, Env, Environment};
+use super::limits;
+use super::namespace;
+use super::resolve;
+use super::symbol::{self, SymbolId};
+use super::types;
+use super::types::MalType::*;
+use super::types::{
+    err_str, err_string, name_function, new_false, new_fn_clauses, new_hash, new_lazy_seq,
+    new_list, new_list_with_source, new_mal_function_clauses, new_nil, new_set, new_true,
+    new_vector, Arity, Binding, FnClause, MalError, MalErrorKind, MalHashContainer, MalHashKey,
+    MalList, MalResult, MalSetContainer, MalValue,
+};
+
+/// Checked fn* forms, by address (see 'CHECKED_FNS'): their arities, and
+/// whether their bodies were resolved (see 'set_lexical_addressing').
+type CheckedFns = HashMap<*const types::MalType, (Weak<types::MalType>, bool, Rc<Vec<FnClause>>)>;
+
+thread_local! {
+    static LEXICAL_ADDRESSING: Cell<bool> = const { Cell::new(false) };
+    /// The fn* forms still alive already checked, by address.
+    ///
+    /// The forms are weakly held, which is enough to prevent their address
+    /// from being reused.
+    static CHECKED_FNS: RefCell<CheckedFns> = RefCell::new(HashMap::new());
+    /// The number of checked forms above which the dead ones are dropped.
+    static CHECKED_FNS_LIMIT: Cell<usize> = const { Cell::new(64) };
+}
+
+/// Enable or disable (the default) the resolution of the symbols of the
+/// functions bodies to their lexical address when the functions are created
+/// (for the current thread).
+pub fn set_lexical_addressing(enabled: bool) {
+    LEXICAL_ADDRESSING.with(|flag| flag.set(enabled));
+}
+
+/// Evaluate the items of a collection, or resolve a symbol (qualified by
+/// its namespace if bound to none in the given environment).
+fn eval_ast(ast: MalValue, env: &Env) -> MalResult {
+    match *ast {
+        Symbol(symbol) => env
+            .get_env_value(&ast)
+            .or_else(|why| namespace::lookup_qualified(env, symbol).ok_or(why)),
+        LocalSymbol(symbol, depth) => match env::lookup_at(env, symbol, depth) {
+            Some(value) => Ok(value),
+            None => err_string(
+                MalErrorKind::UnboundSymbol,
+                format!("env: '{}' not found", symbol),
+            ),
+        },
+        List(ref seq, _) | Vector(ref seq) => {
+            let mut ast_ev = vec![];
+            for value in seq {
+                ast_ev.push(eval(value.clone(), env.clone())?);
+            }
+            Ok(match *ast {
+                List(..) => new_list(ast_ev),
+                _ => new_vector(ast_ev),
+            })
+        }
+        Hash(ref hash) => {
+            let mut hash_ev = MalHashContainer::new();
+            for (key, value) in hash {
+                hash_ev.insert(key.clone(), eval(value.clone(), env.clone())?);
+            }
+            Ok(new_hash(hash_ev))
+        }
+        Set(ref set) => {
+            let mut set_ev = MalSetContainer::new();
+            for item in set {
+                set_ev.insert(MalHashKey::new(eval(item.value().clone(), env.clone())?));
+            }
+            Ok(new_set(set_ev))
+        }
+        _ => Ok(ast.clone()),
+    }
+}
+
+/// Return the (unevaluated) symbol argument of the given special form call,
+/// which must have no other argument.
+fn symbol_arg(args: &MalList, name: &str) -> MalResult {
+    if args.len() != 2 {
+        return err_string(
+            MalErrorKind::Arity,
+            format!("wrong arity for {}, should be 1", name),
+        );
+    }
+    match *args[1] {
+        Symbol(_) => Ok(args[1].clone()),
+        LocalSymbol(symbol, _) => Ok(Rc::new(Symbol(symbol))),
+        _ => err_string(
+            MalErrorKind::Syntax,
+            format!("{} with non-symbol as a key", name),
+        ),
+    }
+}
+
+/// Return whether the arguments of the given fn* form are arity clauses,
+/// `(fn* (params0 body0) (params1 body1) ...)`, rather than the parameters
+/// and body of a single arity.
+pub fn is_multi_arity(args: &MalList) -> bool {
+    args.len() > 1
+        && args.iter().skip(1).all(|clause| match **clause {
+            List(ref clause, _) => matches!(
+                clause.front().map(|params| &**params),
+                Some(List(..)) | Some(Vector(_))
+            ),
+            _ => false,
+        })
+}
+
+/// Check the arguments of the given fn* form and return its arity clauses,
+/// the parameters and body of each arity (a single one if not given as
+/// clauses).
+///
+/// The parameters lists and the bodies are checked when the first function
+/// is created from the form (see 'checked_fn'): each symbol is bound once, a single pattern follows '&' and recur
+/// is only called in tail position of a loop of the body (the body itself
+/// not being one).
+pub fn check_fn(args: &MalList) -> Result<Vec<(MalValue, MalValue)>, MalError> {
+    let clauses = if is_multi_arity(args) {
+        args.iter()
+            .skip(1)
+            .map(|clause| match **clause {
+                List(ref clause, _) if clause.len() == 2 => {
+                    Ok((clause[0].clone(), clause[1].clone()))
+                }
+                _ => Err(MalError::new(
+                    MalErrorKind::Arity,
+                    "wrong arity for an fn* arity clause, should be 2",
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        if args.len() != 3 {
+            return Err(MalError::new(
+                MalErrorKind::Arity,
+                "wrong arity for fn*, should be 2",
+            ));
+        }
+        match *args[1] {
+            List(..) | Vector(_) => vec![(args[1].clone(), args[2].clone())],
+            _ => {
+                return Err(MalError::new(
+                    MalErrorKind::Syntax,
+                    "fn* with non-list arguments",
+                ))
+            }
+        }
+    };
+    let mut arities: Vec<Arity> = vec![];
+    for (params, body) in &clauses {
+        destructure::parse_params(params)?;
+        check_recur(body, None)?;
+        let arity = Arity::of_params(params);
+        let conflict = if arity.variadic {
+            arities
+                .iter()
+                .any(|other| other.variadic || other.required > arity.required)
+        } else {
+            arities
+                .iter()
+                .any(|other| (other.variadic && other.required < arity.required) || *other == arity)
+        };
+        if conflict {
+            return Err(MalError::new(
+                MalErrorKind::Syntax,
+                format!("conflicting arity {} in the fn* arity clauses", arity),
+            ));
+        }
+        arities.push(arity);
+    }
+    Ok(clauses)
+}
+
+/// Return the arities of the given fn* form (of the given items), checking
+/// (and resolving, with lexical addressing) it only the first time.
+fn checked_fn(ast: &MalValue, args: &MalList) -> Result<Rc<Vec<FnClause>>, MalError> {
+    let key = Rc::as_ptr(ast);
+    let resolved = LEXICAL_ADDRESSING.with(Cell::get);
+    let cached = CHECKED_FNS.with(|checked| match checked.borrow().get(&key) {
+        Some((_, was_resolved, clauses)) if *was_resolved == resolved => Some(clauses.clone()),
+        _ => None,
+    });
+    if let Some(clauses) = cached {
+        return Ok(clauses);
+    }
+    let mut clauses = check_fn(args)?;
+    if resolved {
+        for (params, body) in clauses.iter_mut() {
+            *body = resolve::resolve_fn(params, body);
+        }
+    }
+    let clauses = new_fn_clauses(clauses);
+    CHECKED_FNS.with(|checked| {
+        let mut checked = checked.borrow_mut();
+        if checked.len() >= CHECKED_FNS_LIMIT.with(Cell::get) {
+            checked.retain(|_, (form, _, _)| form.strong_count() > 0);
+            CHECKED_FNS_LIMIT.with(|limit| limit.set((2 * checked.len()).max(64)));
+        }
+        checked.insert(key, (Rc::downgrade(ast), resolved, clauses.clone()));
+    });
+    Ok(clauses)
+}
+
+/// Return the def! form the given defn form stands for:
+/// `(defn name docstring? params body)` (or with arity clauses after the
+/// name) defines `(def! name docstring? (fn* params body))`.
+pub fn expand_defn(ast: &MalValue, args: &MalList) -> MalResult {
+    let name = match args.get(1).map(|name| &**name) {
+        Some(&Symbol(_)) => args[1].clone(),
+        Some(_) => return err_str(MalErrorKind::Syntax, "defn with non-symbol as a name"),
+        None => {
+            return err_str(
+                MalErrorKind::Arity,
+                "wrong arity for defn, should be at least 2",
+            )
+        }
+    };
+    let mut rest = args.skip(2);
+    let doc = match rest.front().map(|doc| &**doc) {
+        Some(&Str(_)) if rest.len() > 1 => rest.pop_front(),
+        _ => None,
+    };
+    rest.push_front(Rc::new(Symbol(symbol::FN)));
+    let function = new_list_with_source(rest, types::source_of(ast));
+    let mut definition = vec![Rc::new(Symbol(symbol::DEF)), name];
+    definition.extend(doc);
+    definition.push(function);
+    Ok(new_list(definition))
+}
+
+/// Check the arguments of the given loop form, `(loop (key0 value0 key1
+/// value1 ...) body)` : bindings as the let* ones and a body calling recur
+/// only in tail position, with one argument per binding. Return its keys,
+/// parsed as binding patterns.
+///
+/// The evaluators check a loop form before evaluating it, so that a
+/// misplaced recur is reported even if it is not reached.
+pub fn check_loop(args: &MalList) -> Result<Vec<Pattern>, MalError> {
+    if args.len() != 3 {
+        return Err(MalError::new(
+            MalErrorKind::Arity,
+            "wrong arity for loop, should be 2",
+        ));
+    }
+    let bindings = match *args[1] {
+        List(ref bindings, _) | Vector(ref bindings) => bindings,
+        _ => {
+            return Err(MalError::new(
+                MalErrorKind::Syntax,
+                "loop with non-list binding",
+            ))
+        }
+    };
+    if bindings.len() % 2 != 0 {
+        return Err(MalError::new(
+            MalErrorKind::Syntax,
+            "missing key or value in the loop binding list",
+        ));
+    }
+    let mut keys = vec![];
+    for (key, value) in bindings
+        .iter()
+        .step_by(2)
+        .zip(bindings.iter().skip(1).step_by(2))
+    {
+        keys.push(destructure::parse(key)?);
+        check_recur(value, None)?;
+    }
+    check_recur(&args[2], Some(keys.len()))?;
+    Ok(keys)
+}
+
+/// Check that the given form only calls recur in tail position of a loop,
+/// given the number of its bindings if the form is itself in tail position
+/// of one.
+fn check_recur(ast: &MalValue, tail: Option<usize>) -> Result<(), MalError> {
+    let seq = match **ast {
+        List(ref seq, _) => seq,
+        Vector(ref seq) => return seq.iter().try_for_each(|item| check_recur(item, None)),
+        Hash(ref hash) => return hash.values().try_for_each(|value| check_recur(value, None)),
+        Set(ref set) => {
+            return set
+                .iter()
+                .try_for_each(|item| check_recur(item.value(), None))
+        }
+        _ => return Ok(()),
+    };
+    let head = match seq.front().map(|head| &**head) {
+        Some(&Symbol(head)) => Some(head),
+        _ => None,
+    };
+    let check_all = |items: MalList| items.iter().try_for_each(|item| check_recur(item, None));
+    match head {
+        Some(symbol::RECUR) => match tail {
+            Some(count) if count == seq.len() - 1 => check_all(seq.skip(1)),
+            Some(count) => Err(MalError::new(
+                MalErrorKind::Arity,
+                format!("wrong arity for recur, should be {}", count),
+            )),
+            None => Err(MalError::new(
+                MalErrorKind::Syntax,
+                "recur must be in tail position of a loop",
+            )),
+        },
+        Some(symbol::LOOP) => check_loop(seq).map(|_| ()),
+        Some(symbol::DO) if seq.len() > 1 => {
+            check_all(seq.skip(1).take(seq.len() - 2))?;
+            check_recur(seq.last().unwrap(), tail)
+        }
+        Some(symbol::IF) if seq.len() > 1 => {
+            check_recur(&seq[1], None)?;
+            seq.iter()
+                .skip(2)
+                .try_for_each(|branch| check_recur(branch, tail))
+        }
+        Some(symbol::LET) if seq.len() == 3 => {
+            if let List(ref bindings, _) | Vector(ref bindings) = *seq[1] {
+                check_all(bindings.clone())?;
+            }
+            check_recur(&seq[2], tail)
+        }
+        // the body of a function (as the other forms) is not in tail
+        // position of the loop
+        Some(symbol::FN) if is_multi_arity(seq) => {
+            seq.iter().skip(1).try_for_each(|clause| match **clause {
+                List(ref clause, _) => check_all(clause.skip(1)),
+                _ => Ok(()),
+            })
+        }
+        Some(symbol::FN) => check_all(seq.skip(2)),
+        _ => check_all(seq.clone()),
+    }
+}
+
+/// Evaluate the given AST in the given environment.
+///
+/// The special forms and the MAL functions calls in tail position are
+/// evaluated in a loop rather than recursively (tail call optimization).
+pub fn eval(ast: MalValue, env: Env) -> MalResult {
+    let _depth = limits::enter()?;
+    let mut frame = None;
+    eval_tco(ast, env, &mut frame).map_err(|why| match frame {
+        Some(name) => why.with_frame(name),
+        None => why,
+    })
+}
+
+/// The loop form whose body is being evaluated in tail position, which
+/// recur starts again.
+struct LoopTarget {
+    keys: Vec<Pattern>,
+    body: MalValue,
+    /// The environment the loop form is evaluated in.
+    env: Env,
+}
+
+/// The evaluation loop of 'eval', keeping track in frame of the name of the
+/// MAL function whose body is being evaluated (if any).
+fn eval_tco(mut ast: MalValue, mut env: Env, frame: &mut Option<String>) -> MalResult {
+    let mut target: Option<LoopTarget> = None;
+    loop {
+        limits::step()?;
+        let ast_temp = ast.clone();
+        let (arg0_symbol, args): (Option<SymbolId>, &MalList) = match *ast_temp {
+            List(ref seq, _) => {
+                if seq.is_empty() {
+                    return Ok(ast);
+                }
+                match *seq[0] {
+                    Symbol(symbol) => (Some(symbol), seq),
+                    _ => (None, seq),
+                }
+            }
+            _ => return eval_ast(ast, &env),
+        };
+
+        match arg0_symbol {
+            // (do items...) : evaluate all items and return the last one
+            Some(symbol::DO) => {
+                if args.len() < 2 {
+                    return Ok(new_nil());
+                }
+                for item in args.iter().skip(1).take(args.len() - 2) {
+                    eval(item.clone(), env.clone())?;
+                }
+                ast = args.last().unwrap().clone();
+                continue;
+            }
+            // (if condition if_condition_not_nil_or_false otherwise)
+            // if 'otherwise' is not provided, return nil if 'condition'
+            // evaluates to nil or false
+            Some(symbol::IF) => {
+                if args.len() < 3 || args.len() > 4 {
+                    return err_str(MalErrorKind::Arity, "wrong arity for if, should be 3 or 4");
+                }
+                match *eval(args[1].clone(), env.clone())? {
+                    False | Nil => {
+                        if args.len() == 4 {
+                            ast = args[3].clone();
+                            continue;
+                        }
+                        return Ok(new_nil());
+                    }
+                    _ => {
+                        ast = args[2].clone();
+                        continue;
+                    }
+                }
+            }
+            // (def! key docstring? value) ; key must be a Symbol
+            // bind the evaluated value in env with the unevaluated key
+            // (a function value is named after the key and documented with
+            // the docstring, if any)
+            Some(symbol::DEF) => {
+                let doc = match args.len() {
+                    3 => None,
+                    4 => match *args[2] {
+                        Str(ref doc) => Some(doc.clone()),
+                        _ => {
+                            return err_str(MalErrorKind::Syntax, "def! with non-string docstring")
+                        }
+                    },
+                    _ => {
+                        return err_str(
+                            MalErrorKind::Arity,
+                            "wrong arity for def!, should be 2 or 3",
+                        )
+                    }
+                };
+                let key = args[1].clone();
+                let value = eval(args.last().unwrap().clone(), env.clone())?;
+                return match *key {
+                    Symbol(symbol) => {
+                        let value = name_function(value, &symbol.name(), doc);
+                        env.set_env_value(key.clone(), value.clone());
+                        Ok(value)
+                    }
+                    _ => err_str(MalErrorKind::Syntax, "def! with non-symbol as a key"),
+                };
+            }
+            // (undef! key) ; key must be a Symbol
+            // remove the binding of the unevaluated key from the innermost
+            // environment binding it, and return whether there was one
+            Some(symbol::UNDEF) => {
+                let key = symbol_arg(args, "undef!")?;
+                let removed = env::chain(&env).any(|mut env| env.remove_env_value(&key).is_some());
+                return Ok(if removed { new_true() } else { new_false() });
+            }
+            // (resolve key) : return the value bound to the unevaluated key,
+            // nil if none
+            Some(symbol::RESOLVE) => {
+                let key = symbol_arg(args, "resolve")?;
+                return Ok(eval_ast(key, &env).unwrap_or_else(|_| new_nil()));
+            }
+            // (bound? key) : return whether the unevaluated key is bound
+            Some(symbol::BOUND) => {
+                let key = symbol_arg(args, "bound?")?;
+                return Ok(match eval_ast(key, &env) {
+                    Ok(_) => new_true(),
+                    Err(_) => new_false(),
+                });
+            }
+            // (ns-publics) : return the hash map of the symbols bound in the
+            // current namespace (or the root environment if none) to their
+            // values
+            Some(symbol::NS_PUBLICS) => {
+                if args.len() != 1 {
+                    return err_str(
+                        MalErrorKind::Arity,
+                        "wrong arity for ns-publics, should be 0",
+                    );
+                }
+                return Ok(new_hash(
+                    env::namespace_env(&env)
+                        .unwrap_or_else(|| env.root())
+                        .bindings()
+                        .into_iter()
+                        .map(|(key, value)| (MalHashKey::new(key), value))
+                        .collect::<MalHashContainer>(),
+                ));
+            }
+            // (ns name clauses...) : switch the evaluation of the top-level
+            // forms to the given namespace, created if needed, then apply
+            // the (:require specs...) clauses in it
+            Some(symbol::NS) => {
+                return match args.get(1).map(|name| &**name) {
+                    Some(&Symbol(name)) => {
+                        let clauses = args.iter().skip(2).cloned().collect::<Vec<_>>();
+                        namespace::switch(&env, &name.name(), &clauses).map(|_| new_nil())
+                    }
+                    _ => err_str(MalErrorKind::Syntax, "ns with non-symbol as a name"),
+                };
+            }
+            // (require specs...) : load the given namespaces (once) and
+            // apply their aliases and referrals in the current namespace
+            Some(symbol::REQUIRE) => {
+                return namespace::require(&env, &args.skip(1)).map(|_| new_nil());
+            }
+            // (let* (key0 value0 key1 value1 ...) value)
+            // evaluate value in a temporary sub-environment where
+            // the given (key: Symbol or binding pattern / value: _) pairs
+            // are set
+            Some(symbol::LET) => {
+                if args.len() != 3 {
+                    return err_str(MalErrorKind::Arity, "wrong arity for \"let*\", should be 2");
+                }
+                let mut env_let = env.new_inner();
+                match *args[1] {
+                    List(ref bindings_seq, _) | Vector(ref bindings_seq) => {
+                        if bindings_seq.len() % 2 != 0 {
+                            return err_str(
+                                MalErrorKind::Syntax,
+                                concat!("missing key or value ", "in the let* binding list"),
+                            );
+                        }
+                        let mut it = bindings_seq.iter();
+                        while let (Some(key), Some(expr)) = (it.next(), it.next()) {
+                            match **key {
+                                Symbol(symbol) => {
+                                    let value = eval(expr.clone(), env_let.clone())?;
+                                    let value = name_function(value, &symbol.name(), None);
+                                    env_let.set_env_value(key.clone(), value);
+                                }
+                                _ => {
+                                    let pattern = destructure::parse(key)?;
+                                    let value = eval(expr.clone(), env_let.clone())?;
+                                    pattern.bind_env(&env_let, value, eval)?;
+                                }
+                            }
+                        }
+                    }
+                    _ => return err_str(MalErrorKind::Syntax, "let* with non-list binding"),
+                }
+                ast = args[2].clone();
+                env = env_let;
+                continue;
+            }
+            // (loop (key0 value0 key1 value1 ...) body)
+            // evaluate body as let* does, each recur in it (checked to be
+            // in tail position) evaluating it again with the keys bound to
+            // the recur arguments
+            Some(symbol::LOOP) => {
+                let keys = check_loop(args)?;
+                let env_loop = env.new_inner();
+                if let List(ref bindings, _) | Vector(ref bindings) = *args[1] {
+                    for (key, expr) in keys.iter().zip(bindings.iter().skip(1).step_by(2)) {
+                        let value = eval(expr.clone(), env_loop.clone())?;
+                        key.bind_env(&env_loop, value, eval)?;
+                    }
+                }
+                target = Some(LoopTarget {
+                    keys,
+                    body: args[2].clone(),
+                    env,
+                });
+                ast = args[2].clone();
+                env = env_loop;
+                continue;
+            }
+            // (recur values...) : evaluate the body of the loop again
+            Some(symbol::RECUR) => {
+                let target = match target {
+                    Some(ref target) => target,
+                    None => {
+                        return err_str(
+                            MalErrorKind::Syntax,
+                            "recur must be in tail position of a loop",
+                        )
+                    }
+                };
+                let mut values = vec![];
+                for arg in args.iter().skip(1) {
+                    values.push(eval(arg.clone(), env.clone())?);
+                }
+                let env_loop = target.env.new_inner();
+                for (key, value) in target.keys.iter().zip(values) {
+                    key.bind_env(&env_loop, value, eval)?;
+                }
+                ast = target.body.clone();
+                env = env_loop;
+                continue;
+            }
+            // (fn* (args...) exp) or (fn* ((args...) exp)...)
+            Some(symbol::FN) => {
+                return Ok(new_mal_function_clauses(
+                    self::eval,
+                    env,
+                    checked_fn(&ast, args)?,
+                    types::source_of(&ast),
+                    Binding::Named,
+                ));
+            }
+            // (defn name docstring? args...) : define the function named as
+            // given, of the arguments of fn*
+            Some(symbol::DEFN) => {
+                ast = expand_defn(&ast, args)?;
+                continue;
+            }
+            // (lazy-seq body...) : defer the evaluation of body (which must
+            // return a sequence) to the first access to the sequence
+            Some(symbol::LAZY_SEQ) => {
+                let mut body = args.skip(1);
+                body.push_front(Rc::new(Symbol(symbol::DO)));
+                let body = new_list(body);
+                return Ok(new_lazy_seq(Box::new(move || eval(body, env))));
+            }
+            // otherwise : apply the first item to the other
+            _ => (),
+        }
+
+        let list_ev = eval_ast(ast, &env)?;
+        let items = match *list_ev {
+            List(ref seq, _) => seq,
+            _ => return err_str(MalErrorKind::Type, "can only apply on a list"),
+        };
+        let f = &items[0];
+        let f_args = items.iter().skip(1).cloned().collect();
+        match **f {
+            MalFunction(ref data) if data.binds_by_name() => {
+                let (exp, eval_env) = data.bind(f_args)?;
+                *frame = Some(data.frame_name().to_string());
+                target = None;
+                ast = exp;
+                env = eval_env;
+            }
+            _ => return f.apply(f_args),
+        }
+    }
+}
@@ -0,0 +1,261 @@
+/// Module collecting the reference cycles between the environments and the
+/// MAL functions, which reference counting alone never frees: a function
+/// bound in the environment it was created in (e.g. a recursive function
+/// bound by let*, or defined by def! in a function body) keeps it alive, and
+/// is kept alive by it. The namespaces environments of an interpreter also
+/// keep alive its registry (see 'namespace'), which keeps them alive.
+///
+/// Every environment is tracked (weakly) when created. The collection counts,
+/// for each tracked environment and each function bound in one, the
+/// references coming from the other ones: those with more references are
+/// referred to from elsewhere (the evaluation in progress, a collection, the
+/// embedder...) and are kept, along with all the environments reachable from
+/// them. The other environments are garbage cycles, and are cleared.
+///
+/// The registries referenced by the tracked namespaces are counted the same
+/// way, so that the environments of a dropped interpreter are collected too.
+///
+/// The references not traced (e.g. a function in a list) only keep more
+/// environments alive, so that the collection is safe at any time. It runs
+/// when the number of tracked environments doubles, or on demand.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::env::{self, Env, EnvData};
+use super::namespace::{self, Registry};
+use super::types::MalType::MalFunction;
+
+/// The memory statistics of the environments of the current thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The number of live environments.
+    pub envs: usize,
+    /// The number of collections run.
+    pub collections: usize,
+    /// The number of environments cleared by the collections.
+    pub collected: usize,
+}
+
+/// The minimum number of tracked environments above which a collection runs.
+const MIN_LIMIT: usize = 1024;
+
+thread_local! {
+    /// The environments created, weakly held.
+    static ENVS: RefCell<Vec<Weak<RefCell<EnvData>>>> = const { RefCell::new(vec![]) };
+    /// The number of tracked environments above which a collection runs.
+    static LIMIT: Cell<usize> = const { Cell::new(MIN_LIMIT) };
+    static STATS: Cell<MemoryStats> = Cell::new(MemoryStats::default());
+}
+
+/// Track the given new environment, collecting the cycles if there are
+/// enough tracked environments since the last collection.
+pub fn track(env: &Env) {
+    let full = ENVS.with(|envs| {
+        let mut envs = envs.borrow_mut();
+        envs.push(Rc::downgrade(env));
+        envs.len() >= LIMIT.with(Cell::get)
+    });
+    if full {
+        let live = collect();
+        LIMIT.with(|limit| limit.set((2 * live).max(MIN_LIMIT)));
+    }
+}
+
+/// Return the memory statistics of the current thread.
+pub fn stats() -> MemoryStats {
+    let envs = ENVS.with(|envs| {
+        envs.borrow()
+            .iter()
+            .filter(|env| env.strong_count() > 0)
+            .count()
+    });
+    STATS.with(|stats| MemoryStats {
+        envs,
+        ..stats.get()
+    })
+}
+
+/// A tracked environment, during a collection.
+struct EnvNode {
+    env: Env,
+    /// The number of references from the other tracked nodes.
+    internal: usize,
+    /// The outer environment, the functions bound and the registry of its
+    /// namespace, by index.
+    outer: Option<usize>,
+    functions: Vec<usize>,
+    registry: Option<usize>,
+    /// False if it could not be traced (being modified), and is thus kept.
+    traced: bool,
+    reachable: bool,
+}
+
+/// A function bound in a tracked environment, during a collection.
+struct FunctionNode {
+    strong: usize,
+    internal: usize,
+    /// Its outer environment, by index (if tracked).
+    env: Option<usize>,
+}
+
+/// The registry of the namespaces of an interpreter, during a collection.
+struct RegistryNode {
+    registry: Rc<Registry>,
+    /// The number of references from the namespaces of the tracked nodes.
+    internal: usize,
+    /// True if referenced from elsewhere (e.g. a namespace in use), or if it
+    /// could not be traced.
+    external: bool,
+    /// The environments of its namespaces, by index.
+    envs: Vec<usize>,
+    reachable: bool,
+}
+
+/// Collect the garbage cycles of environments (and the functions bound in
+/// them), and return the number of live environments.
+pub fn collect() -> usize {
+    let envs = ENVS.with(|envs| {
+        let mut envs = envs.borrow_mut();
+        envs.retain(|env| env.strong_count() > 0);
+        envs.iter().filter_map(Weak::upgrade).collect::<Vec<_>>()
+    });
+    let indices = envs
+        .iter()
+        .enumerate()
+        .map(|(i, env)| (Rc::as_ptr(env), i))
+        .collect::<HashMap<_, _>>();
+    let mut nodes = envs
+        .into_iter()
+        .map(|env| EnvNode {
+            env,
+            internal: 0,
+            outer: None,
+            functions: vec![],
+            registry: None,
+            traced: false,
+            reachable: false,
+        })
+        .collect::<Vec<_>>();
+    let mut functions: Vec<FunctionNode> = vec![];
+    let mut function_indices = HashMap::new();
+    let mut registries: Vec<RegistryNode> = vec![];
+    let mut registry_indices = HashMap::new();
+
+    // count the references between the tracked nodes
+    for i in 0..nodes.len() {
+        let (mut outer, mut bound, mut registry) = (None, vec![], None);
+        nodes[i].traced = env::trace(
+            &nodes[i].env,
+            &mut |outer_env| outer = indices.get(&Rc::as_ptr(outer_env)).cloned(),
+            &mut |value| {
+                if let MalFunction(ref data) = **value {
+                    let index = *function_indices
+                        .entry(Rc::as_ptr(value))
+                        .or_insert_with(|| {
+                            functions.push(FunctionNode {
+                                strong: Rc::strong_count(value),
+                                internal: 0,
+                                env: indices.get(&Rc::as_ptr(data.env())).cloned(),
+                            });
+                            functions.len() - 1
+                        });
+                    functions[index].internal += 1;
+                    bound.push(index);
+                }
+            },
+            &mut |env_namespace| {
+                let ns_registry = namespace::registry(env_namespace);
+                let index = *registry_indices
+                    .entry(Rc::as_ptr(ns_registry))
+                    .or_insert_with(|| {
+                        registries.push(RegistryNode {
+                            registry: ns_registry.clone(),
+                            internal: 0,
+                            external: false,
+                            envs: vec![],
+                            reachable: false,
+                        });
+                        registries.len() - 1
+                    });
+                registries[index].internal += 1;
+                registries[index].external |= Rc::strong_count(env_namespace) > 1;
+                registry = Some(index);
+            },
+        );
+        if let Some(outer) = outer {
+            nodes[outer].internal += 1;
+        }
+        nodes[i].outer = outer;
+        nodes[i].functions = bound;
+        nodes[i].registry = registry;
+    }
+    for function in &functions {
+        if let Some(env) = function.env {
+            nodes[env].internal += 1;
+        }
+    }
+    // (the registries are each referenced once by 'registries')
+    for registry in &mut registries {
+        let mut envs = vec![];
+        let traced = namespace::trace(&registry.registry, &mut |ns_env| {
+            envs.extend(indices.get(&Rc::as_ptr(ns_env)).cloned())
+        });
+        registry.external |=
+            !traced || Rc::strong_count(&registry.registry) - 1 > registry.internal;
+        for &env in &envs {
+            nodes[env].internal += 1;
+        }
+        registry.envs = envs;
+    }
+
+    // keep the nodes referenced from elsewhere, and those reachable from them
+    // (the tracked environments are each referenced once by 'nodes')
+    let mut pending = nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| !node.traced || Rc::strong_count(&node.env) - 1 > node.internal)
+        .map(|(i, _)| i)
+        .chain(
+            functions
+                .iter()
+                .filter(|function| function.strong > function.internal)
+                .filter_map(|function| function.env),
+        )
+        .collect::<Vec<_>>();
+    for registry in registries.iter_mut().filter(|registry| registry.external) {
+        registry.reachable = true;
+        pending.extend(&registry.envs);
+    }
+    while let Some(i) = pending.pop() {
+        if nodes[i].reachable {
+            continue;
+        }
+        nodes[i].reachable = true;
+        pending.extend(nodes[i].outer);
+        pending.extend(
+            nodes[i]
+                .functions
+                .iter()
+                .filter_map(|&function| functions[function].env),
+        );
+        if let Some(registry) = nodes[i].registry {
+            if !registries[registry].reachable {
+                registries[registry].reachable = true;
+                pending.extend(&registries[registry].envs);
+            }
+        }
+    }
+
+    let garbage = nodes.iter().filter(|node| !node.reachable).count();
+    for node in nodes.iter().filter(|node| !node.reachable) {
+        env::clear(&node.env);
+    }
+    STATS.with(|stats| {
+        let mut updated = stats.get();
+        updated.collections += 1;
+        updated.collected += garbage;
+        stats.set(updated);
+    });
+    nodes.len() - garbage
+}
@@ -10,11 +10,22 @@ extern crate log;
 #[macro_use]
 extern crate lazy_static;
 
+pub mod analyzer;
 pub mod core;
+pub mod destructure;
+pub mod edn;
 pub mod env;
+pub mod eval;
+pub mod gc;
+pub mod limits;
+pub mod namespace;
 pub mod printer;
 pub mod reader;
+pub mod resolve;
+pub mod seq;
+pub mod symbol;
 pub mod types;
+pub mod vm;
 
 pub mod readline {
     use std::io;
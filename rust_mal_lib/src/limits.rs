@@ -0,0 +1,193 @@
+/// Module bounding the evaluation of (possibly untrusted) code, for the
+/// current thread: the evaluation steps, the depth of the nested calls, the
+/// size of the collections built and the wall-clock time of each top-level
+/// evaluation, which can also be interrupted from another thread.
+///
+/// The limits are cooperative: the evaluation backends count their steps and
+/// calls here, and exceeding a limit raises a 'MalErrorKind::Limit' error,
+/// leaving the environments usable for the next evaluation.
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::types::{MalError, MalErrorKind};
+
+/// The limits of an evaluation, none by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The number of evaluation steps (iterations of the evaluation loop of
+    /// the backend, or realized lazy sequence items).
+    pub steps: Option<u64>,
+    /// The depth of the nested evaluations (roughly the non-tail calls).
+    pub depth: Option<usize>,
+    /// The number of items of a collection (or characters of a string) built
+    /// by a core function.
+    pub collection_size: Option<usize>,
+    /// The wall-clock time.
+    pub time: Option<Duration>,
+}
+
+/// The number of steps between two checks of the clock.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// A monotonic clock: the time elapsed since an arbitrary, fixed origin.
+pub type Clock = fn() -> Duration;
+
+/// The default clock, measuring the time elapsed since its first reading.
+#[cfg(not(target_arch = "wasm32"))]
+fn system_clock() -> Duration {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed()
+}
+
+/// There is no default clock on WebAssembly, where 'std::time::Instant'
+/// panics: the host must supply one (see 'set_clock').
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_CLOCK: Option<Clock> = Some(system_clock);
+#[cfg(target_arch = "wasm32")]
+const DEFAULT_CLOCK: Option<Clock> = None;
+
+struct State {
+    limits: Cell<Limits>,
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    clock: Cell<Option<Clock>>,
+    deadline: Cell<Option<Duration>>,
+    interrupted: RefCell<Arc<AtomicBool>>,
+}
+
+thread_local! {
+    static STATE: State = State {
+        limits: Cell::new(Limits::default()),
+        steps: Cell::new(0),
+        depth: Cell::new(0),
+        clock: Cell::new(DEFAULT_CLOCK),
+        deadline: Cell::new(None),
+        interrupted: RefCell::new(Arc::new(AtomicBool::new(false))),
+    };
+}
+
+fn exceeded(message: String) -> MalError {
+    MalError::new(MalErrorKind::Limit, message)
+}
+
+/// Set the limits of the evaluations of the current thread.
+pub fn set_limits(limits: Limits) {
+    STATE.with(|state| state.limits.set(limits));
+}
+
+/// Return the limits of the evaluations of the current thread.
+pub fn limits() -> Limits {
+    STATE.with(|state| state.limits.get())
+}
+
+/// Set the clock measuring the time limit of the evaluations of the current
+/// thread. Without a clock (the default on WebAssembly), the time limit is
+/// not enforced.
+pub fn set_clock(clock: Clock) {
+    STATE.with(|state| state.clock.set(Some(clock)));
+}
+
+/// Start the budget of a new top-level evaluation: reset the steps count and
+/// the clock, and clear the interrupt flag.
+pub fn start() {
+    STATE.with(|state| {
+        state.steps.set(0);
+        let time = state.limits.get().time;
+        let clock = state.clock.get();
+        state
+            .deadline
+            .set(time.zip(clock).map(|(time, clock)| clock() + time));
+        state.interrupted.borrow().store(false, Ordering::Relaxed);
+    });
+}
+
+/// Return the interrupt flag of the evaluations of the current thread: once
+/// set (e.g. from a signal handler or another thread), the running
+/// evaluation fails at its next step.
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    STATE.with(|state| state.interrupted.borrow().clone())
+}
+
+/// Count an evaluation step, failing if the steps budget or the time limit
+/// is exceeded or if the evaluation was interrupted.
+pub fn step() -> Result<(), MalError> {
+    STATE.with(|state| {
+        let steps = state.steps.get() + 1;
+        state.steps.set(steps);
+        if state.interrupted.borrow().load(Ordering::Relaxed) {
+            return Err(exceeded("evaluation interrupted".to_string()));
+        }
+        let limits = state.limits.get();
+        match limits.steps {
+            Some(max) if steps > max => {
+                return Err(exceeded(format!(
+                    "evaluation steps limit ({}) exceeded",
+                    max
+                )))
+            }
+            _ => (),
+        }
+        match (limits.time, state.deadline.get(), state.clock.get()) {
+            (Some(time), Some(deadline), Some(clock))
+                if steps % CLOCK_INTERVAL == 0 && clock() > deadline =>
+            {
+                Err(exceeded(format!(
+                    "evaluation time limit ({}ms) exceeded",
+                    time.as_millis()
+                )))
+            }
+            _ => Ok(()),
+        }
+    })
+}
+
+/// A nested evaluation, counted in the depth until dropped (see 'enter').
+pub struct Depth(());
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        STATE.with(|state| state.depth.set(state.depth.get() - 1));
+    }
+}
+
+/// Enter a nested evaluation, failing if the depth limit is exceeded.
+pub fn enter() -> Result<Depth, MalError> {
+    check_depth(1)?;
+    STATE.with(|state| state.depth.set(state.depth.get() + 1));
+    Ok(Depth(()))
+}
+
+/// Check that the given number of calls can be nested in the current
+/// evaluation (e.g. the frames of the virtual machine).
+pub fn check_depth(calls: usize) -> Result<(), MalError> {
+    STATE.with(|state| match state.limits.get().depth {
+        Some(max) if state.depth.get() + calls > max => {
+            Err(exceeded(format!("call depth limit ({}) exceeded", max)))
+        }
+        _ => Ok(()),
+    })
+}
+
+/// Check the size of a collection being built.
+pub fn check_size(size: usize) -> Result<(), MalError> {
+    STATE.with(|state| match state.limits.get().collection_size {
+        Some(max) if size > max => Err(exceeded(format!(
+            "collection size limit ({}) exceeded",
+            max
+        ))),
+        _ => Ok(()),
+    })
+}
+
+/// Check the length (in characters) of a string being built.
+pub fn check_length(string: &str) -> Result<(), MalError> {
+    // only count the characters when there may be too many of them
+    match limits().collection_size {
+        Some(max) if string.len() > max => check_size(string.chars().count()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,387 @@
+/// Module defining the MAL namespaces: named root environments, whose
+/// bindings can be referred to from the other namespaces by qualified symbols
+/// (e.g. `mal.core/map`, or `str/join` with a `str` alias).
+///
+/// Each namespace environment has the 'mal.core' one, populated with the core
+/// functions, as its outer. The namespaces of an interpreter are kept in a
+/// registry, along with the search path of the `.mal` files defining them
+/// (loaded once, on their first 'require').
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::env as std_env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use super::core::{self, Capability};
+use super::env::{self, Env, Environment};
+use super::eval;
+use super::reader;
+use super::symbol::SymbolId;
+use super::types::MalType::*;
+use super::types::{new_str, new_symbol, MalError, MalErrorKind, MalList, MalValue};
+
+/// The name of the namespace of the core functions.
+pub const CORE: &str = "mal.core";
+/// The name of the namespace the REPL starts in.
+pub const USER: &str = "user";
+
+/// The namespaces of an interpreter.
+pub struct Registry {
+    namespaces: RefCell<HashMap<String, Env>>,
+    /// The namespace the top-level forms are evaluated in.
+    current: RefCell<Option<Env>>,
+    /// The directories searched for the namespaces files, in order.
+    search_path: RefCell<Vec<PathBuf>>,
+    /// The namespaces loaded (or being loaded) from a file.
+    loaded: RefCell<HashSet<String>>,
+    /// The capability groups of the core functions bound.
+    capabilities: Vec<Capability>,
+}
+
+/// A namespace, attached to its environment.
+pub struct Namespace {
+    pub name: String,
+    /// The namespaces names by their aliases (given by 'require' with ':as').
+    aliases: RefCell<HashMap<String, String>>,
+    registry: Rc<Registry>,
+}
+
+/// Create the namespaces of a new interpreter ('mal.core', populated with the
+/// core functions, and 'user') and return the 'user' environment.
+///
+/// The search path is the current directory, preceded by the directories of
+/// the `MAL_PATH` environment variable (if any).
+pub fn new_interpreter() -> Env {
+    new_sandbox(&core::CAPABILITIES)
+}
+
+/// Create the namespaces of a new interpreter with only the core functions of
+/// the given capability groups, and return the 'user' environment.
+///
+/// The other core functions are bound in no namespace, and without the
+/// 'IoRead' capability 'require' only refers to the namespaces already
+/// defined (not loading any file).
+pub fn new_sandbox(capabilities: &[Capability]) -> Env {
+    let mut search_path = match std_env::var_os("MAL_PATH") {
+        Some(paths) => std_env::split_paths(&paths).collect(),
+        None => vec![],
+    };
+    search_path.push(PathBuf::from("."));
+    let registry = Rc::new(Registry {
+        namespaces: RefCell::new(HashMap::new()),
+        current: RefCell::new(None),
+        search_path: RefCell::new(search_path),
+        loaded: RefCell::new(HashSet::new()),
+        capabilities: capabilities.to_vec(),
+    });
+    let mut core_env = create(&registry, CORE, None);
+    for (symbol, value) in core::ns_with(capabilities) {
+        core_env.set_env_value(new_symbol(&symbol), value);
+    }
+    let user_env = create(&registry, USER, Some(&core_env));
+    *registry.current.borrow_mut() = Some(user_env.clone());
+    user_env
+}
+
+/// Create and register a new namespace, with the given outer environment.
+fn create(registry: &Rc<Registry>, name: &str, outer: Option<&Env>) -> Env {
+    let mut ns_env = env::new_namespace(
+        outer.cloned(),
+        Namespace {
+            name: name.to_string(),
+            aliases: RefCell::new(HashMap::new()),
+            registry: registry.clone(),
+        },
+    );
+    ns_env.set_env_value(new_symbol("*ns*"), new_str(name.to_string()));
+    registry
+        .namespaces
+        .borrow_mut()
+        .insert(name.to_string(), ns_env.clone());
+    ns_env
+}
+
+/// Return the environment of the given namespace, creating it if needed.
+fn find_or_create(registry: &Rc<Registry>, name: &str) -> Env {
+    if let Some(ns_env) = registry.namespaces.borrow().get(name) {
+        return ns_env.clone();
+    }
+    let core_env = registry.namespaces.borrow().get(CORE).cloned();
+    create(registry, name, core_env.as_ref())
+}
+
+/// Return the registry of the interpreter of the given namespace.
+pub(crate) fn registry(namespace: &Namespace) -> &Rc<Registry> {
+    &namespace.registry
+}
+
+/// Call env with each environment referenced by the given registry (its
+/// namespaces and the current one), and return true, or return false if it is
+/// being modified.
+pub(crate) fn trace(registry: &Registry, env: &mut dyn FnMut(&Env)) -> bool {
+    let (namespaces, current) = match (
+        registry.namespaces.try_borrow(),
+        registry.current.try_borrow(),
+    ) {
+        (Ok(namespaces), Ok(current)) => (namespaces, current),
+        _ => return false,
+    };
+    for ns_env in namespaces.values().chain(current.iter()) {
+        env(ns_env);
+    }
+    true
+}
+
+fn registry_of(env: &Env) -> Result<Rc<Registry>, MalError> {
+    match env::namespace(env) {
+        Some(namespace) => Ok(namespace.registry.clone()),
+        None => Err(MalError::new(
+            MalErrorKind::Value,
+            "no namespace for this environment",
+        )),
+    }
+}
+
+/// Return the environment of the given namespace of the interpreter of the
+/// given environment, if it exists.
+pub fn find(env: &Env, name: &str) -> Option<Env> {
+    let registry = env::namespace(env)?.registry.clone();
+    let ns_env = registry.namespaces.borrow().get(name).cloned();
+    ns_env
+}
+
+/// Return the environment of the namespace the top-level forms should be
+/// evaluated in, for the interpreter of the given environment (the given
+/// environment itself if it has no namespace).
+pub fn current(env: &Env) -> Env {
+    env::namespace(env)
+        .and_then(|namespace| namespace.registry.current.borrow().clone())
+        .unwrap_or_else(|| env.clone())
+}
+
+/// Prepend the given directory to the search path of the interpreter of the
+/// given environment.
+pub fn add_search_path<P: Into<PathBuf>>(env: &Env, path: P) -> Result<(), MalError> {
+    registry_of(env)?
+        .search_path
+        .borrow_mut()
+        .insert(0, path.into());
+    Ok(())
+}
+
+/// Return the value bound to the given qualified symbol (`namespace/name` or
+/// `alias/name`, the aliases being those of the namespace of the given
+/// environment), if any.
+pub fn lookup_qualified(env: &Env, symbol: SymbolId) -> Option<MalValue> {
+    let name = symbol.name();
+    let slash = name.find('/')?;
+    let (ns_name, local_name) = (&name[..slash], &name[slash + 1..]);
+    if ns_name.is_empty() || local_name.is_empty() {
+        return None;
+    }
+    let namespace = env::namespace(env)?;
+    let ns_name = match namespace.aliases.borrow().get(ns_name) {
+        Some(ns_name) => ns_name.clone(),
+        None => ns_name.to_string(),
+    };
+    let ns_env = namespace
+        .registry
+        .namespaces
+        .borrow()
+        .get(&ns_name)?
+        .clone();
+    env::lookup_local(&ns_env, SymbolId::intern(local_name))
+}
+
+/// Return the path, relative to a search path directory, of the file
+/// defining the given namespace (e.g. `my/lib_utils.mal` for `my.lib-utils`).
+fn namespace_file(name: &str) -> PathBuf {
+    let mut path: PathBuf = name.split('.').collect();
+    path.set_extension("mal");
+    PathBuf::from(path.to_string_lossy().replace('-', "_"))
+}
+
+/// Load the given namespace from its file, unless it is already defined (or
+/// being loaded).
+fn ensure_loaded(registry: &Rc<Registry>, name: &str) -> Result<(), MalError> {
+    if registry.namespaces.borrow().contains_key(name)
+        || !registry.loaded.borrow_mut().insert(name.to_string())
+    {
+        return Ok(());
+    }
+    if !registry.capabilities.contains(&Capability::IoRead) {
+        registry.loaded.borrow_mut().remove(name);
+        return Err(MalError::new(
+            MalErrorKind::Value,
+            format!(
+                "require: cannot load namespace {} without the {} capability",
+                name,
+                Capability::IoRead
+            ),
+        ));
+    }
+    let file = namespace_file(name);
+    let path = registry
+        .search_path
+        .borrow()
+        .iter()
+        .map(|directory| directory.join(&file))
+        .find(|path| path.is_file());
+    let result = match path {
+        Some(path) => load_file(registry, name, &path),
+        None => Err(MalError::new(
+            MalErrorKind::Value,
+            format!(
+                "require: cannot find '{}' in the search path for namespace {}",
+                file.display(),
+                name
+            ),
+        )),
+    };
+    if result.is_err() {
+        registry.loaded.borrow_mut().remove(name);
+        registry.namespaces.borrow_mut().remove(name);
+    }
+    result
+}
+
+/// Evaluate all the forms of the given file in the given namespace (or in the
+/// ones it switches to with 'ns'), then switch back to the current namespace.
+fn load_file(registry: &Rc<Registry>, name: &str, path: &PathBuf) -> Result<(), MalError> {
+    let text: Rc<str> = fs::read_to_string(path)
+        .map_err(|why| {
+            MalError::new(
+                MalErrorKind::Value,
+                format!("require: cannot read '{}': {}", path.display(), why),
+            )
+        })?
+        .into();
+    let in_file = |why: MalError| why.in_file(path.display().to_string(), &text);
+    let forms = reader::read_all(&text).map_err(in_file)?;
+    let previous = registry
+        .current
+        .replace(Some(find_or_create(registry, name)));
+    let mut result = Ok(());
+    for form in forms {
+        let ns_env = registry.current.borrow().clone().unwrap();
+        if let Err(why) = eval::eval(form, ns_env) {
+            result = Err(in_file(why));
+            break;
+        }
+    }
+    *registry.current.borrow_mut() = previous;
+    result
+}
+
+/// Switch the top-level forms evaluation to the given namespace (created if
+/// needed), then apply the given 'require' clauses (`(:require specs...)`) in
+/// it.
+pub fn switch(env: &Env, name: &str, clauses: &[MalValue]) -> Result<(), MalError> {
+    let registry = registry_of(env)?;
+    let ns_env = find_or_create(&registry, name);
+    *registry.current.borrow_mut() = Some(ns_env.clone());
+    for clause in clauses {
+        match **clause {
+            List(ref seq, _) if matches!(seq.front().map(|head| &**head), Some(Keyword(ref k)) if k == "require") =>
+            {
+                require(&ns_env, &seq.skip(1))?;
+            }
+            _ => {
+                return Err(MalError::new(
+                    MalErrorKind::Syntax,
+                    format!("ns: unsupported clause {}", clause.pr_str(true)),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Load the namespaces of the given 'require' specs then apply their options
+/// in the namespace of the given environment.
+///
+/// A spec is either a namespace name or a vector `[name options...]`, with as
+/// options `:as alias`, `:refer [names...]` or `:refer :all`.
+pub fn require(env: &Env, specs: &MalList) -> Result<(), MalError> {
+    let namespace = match env::namespace(env) {
+        Some(namespace) => namespace,
+        None => {
+            return Err(MalError::new(
+                MalErrorKind::Value,
+                "require: no namespace for this environment",
+            ))
+        }
+    };
+    let mut ns_env = env::namespace_env(env).unwrap();
+    for spec in specs {
+        let (name, options) = match **spec {
+            Symbol(name) => (name, MalList::new()),
+            Vector(ref seq) => match seq.front().map(|name| &**name) {
+                Some(&Symbol(name)) => (name, seq.skip(1)),
+                _ => return Err(invalid_spec(spec)),
+            },
+            _ => return Err(invalid_spec(spec)),
+        };
+        let name = name.name().to_string();
+        ensure_loaded(&namespace.registry, &name)?;
+        let required_env = namespace.registry.namespaces.borrow().get(&name).cloned();
+        let required_env = required_env.ok_or_else(|| {
+            MalError::new(
+                MalErrorKind::Value,
+                format!("require: namespace {} not found after loading", name),
+            )
+        })?;
+        if options.len() % 2 != 0 {
+            return Err(invalid_spec(spec));
+        }
+        let mut options = options.iter();
+        while let (Some(option), Some(value)) = (options.next(), options.next()) {
+            match (&**option, &**value) {
+                (Keyword(ref k), &Symbol(alias)) if k == "as" => {
+                    namespace
+                        .aliases
+                        .borrow_mut()
+                        .insert(alias.name().to_string(), name.clone());
+                }
+                (Keyword(ref k), Keyword(ref all)) if k == "refer" && all == "all" => {
+                    for (symbol, value) in required_env.bindings() {
+                        if let Symbol(local_name) = *symbol {
+                            if &*local_name.name() != "*ns*" {
+                                ns_env.set_env_value(symbol.clone(), value);
+                            }
+                        }
+                    }
+                }
+                (Keyword(ref k), Vector(ref symbols)) if k == "refer" => {
+                    for symbol in symbols {
+                        let value = match **symbol {
+                            Symbol(local_name) => env::lookup_local(&required_env, local_name),
+                            _ => return Err(invalid_spec(spec)),
+                        };
+                        match value {
+                            Some(value) => {
+                                ns_env.set_env_value(symbol.clone(), value);
+                            }
+                            None => {
+                                return Err(MalError::new(
+                                    MalErrorKind::UnboundSymbol,
+                                    format!("require: {}/{} not found", name, symbol.pr_str(true)),
+                                ))
+                            }
+                        }
+                    }
+                }
+                _ => return Err(invalid_spec(spec)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid_spec(spec: &MalValue) -> MalError {
+    MalError::new(
+        MalErrorKind::Syntax,
+        format!("require: invalid spec {}", spec.pr_str(true)),
+    )
+}
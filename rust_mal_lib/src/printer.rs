@@ -2,8 +2,9 @@ use std::collections::HashMap;
 /// Module allowing to display an AST of 'MalValue'.
 use std::fmt;
 
+use super::seq;
 use super::types::MalType::*;
-use super::types::{new_str, MalHashContainer, MalValue};
+use super::types::{MalHashContainer, MalSetContainer, MalValue};
 
 lazy_static! {
     static ref STR_ESCAPED_CHARS_MAP: HashMap<char, &'static str> = {
@@ -37,10 +38,31 @@ impl super::types::MalType {
                     string.clone()
                 }
             }
-            Symbol(ref string) => string.clone(),
-            List(ref seq) => pr_seq(seq, print_readably, "(", ")", " "),
+            Char(c) => {
+                if print_readably {
+                    pr_char(c)
+                } else {
+                    c.to_string()
+                }
+            }
+            Symbol(symbol) | LocalSymbol(symbol, _) => symbol.name().to_string(),
+            Keyword(ref string) => format!(":{}", string),
+            List(ref seq, _) => pr_seq(seq, print_readably, "(", ")", " "),
             Vector(ref seq) => pr_seq(seq, print_readably, "[", "]", " "),
             Hash(ref hash) => pr_hash(hash, print_readably, "{", "}", " "),
+            Set(ref set) => pr_set(set, print_readably, "#{", "}", " "),
+            LazySeq(_) => match seq::to_vec(self) {
+                Ok(items) => pr_seq(&items, print_readably, "(", ")", " "),
+                Err(why) => format!("#<lazy sequence error: {}>", why),
+            },
+            Tagged(ref tag, ref value) => format!("#{} {}", tag, value.pr_str(print_readably)),
+            Regex(ref regex) => {
+                if print_readably {
+                    format!("#\"{}\"", escape_regex(regex.as_str()))
+                } else {
+                    regex.as_str().to_string()
+                }
+            }
             Function(ref data) => format!("{:?}", data),
             MalFunction(ref data) => format!("{:?}", data),
         }
@@ -53,7 +75,42 @@ impl fmt::Debug for super::types::MalType {
     }
 }
 
-fn pr_seq(seq: &[MalValue], print_readably: bool, start: &str, end: &str, sep: &str) -> String {
+/// Escape the unescaped double quotes of a regex pattern, as in its literal.
+fn escape_regex(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => {
+                escaped.push(c);
+                if let Some(next) = chars.next() {
+                    escaped.push(next);
+                }
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Return the readable representation of a character (e.g. `\a`, `\newline`,
+/// `\u0001` for the other non-printable ones).
+pub fn pr_char(c: char) -> String {
+    match c {
+        '\n' => "\\newline".to_string(),
+        '\r' => "\\return".to_string(),
+        ' ' => "\\space".to_string(),
+        '\t' => "\\tab".to_string(),
+        _ if c.is_control() || c.is_whitespace() => format!("\\u{:04x}", c as u32),
+        _ => format!("\\{}", c),
+    }
+}
+
+fn pr_seq<'a, I>(seq: I, print_readably: bool, start: &str, end: &str, sep: &str) -> String
+where
+    I: IntoIterator<Item = &'a MalValue>,
+{
     let mut string = String::new();
     string.push_str(start);
 
@@ -78,9 +135,22 @@ fn pr_hash(
     end: &str,
     sep: &str,
 ) -> String {
-    let list: Vec<MalValue> = hash
-        .iter()
-        .flat_map(|(k, v)| vec![new_str(k.clone()), v.clone()])
-        .collect();
-    pr_seq(&list, print_readably, start, end, sep)
+    let list = hash.iter().flat_map(|(k, v)| vec![k.value(), v]);
+    pr_seq(list, print_readably, start, end, sep)
+}
+
+fn pr_set(
+    set: &MalSetContainer,
+    print_readably: bool,
+    start: &str,
+    end: &str,
+    sep: &str,
+) -> String {
+    pr_seq(
+        set.iter().map(|k| k.value()),
+        print_readably,
+        start,
+        end,
+        sep,
+    )
 }
@@ -1,34 +1,83 @@
 /// Module permitting the construction of an Abstract Syntax Tree from an
 /// input string.
+use std::rc::Rc;
+
 use regex::Regex;
 
+use super::symbol;
 use super::types;
-use super::types::{err_str, MalError, MalResult, MalValue};
+use super::types::MalType::Symbol;
+use super::types::{
+    err_str, err_string, MalError, MalErrorKind, MalResult, MalValue, Source, Span,
+};
 
+/// Read the first form in the given string.
+///
+/// A reader error spans the last token read when it occurred (or the end of
+/// the string if it was missing a token).
 pub fn read_str(string: &str) -> MalResult {
-    let tokens = tokenize(string);
-    if tokens.is_empty() {
-        Err(MalError::ErrEmptyLine)
+    let mut reader = MalReader::new(string);
+    if reader.tokens.is_empty() {
+        Err(MalError::empty_line())
     } else {
-        let mut reader = MalReader {
-            tokens,
-            position: 0,
-        };
-        read_form(&mut reader)
+        reader.read_form()
     }
 }
 
+/// Read all the forms in the given string (e.g. the content of a file).
+pub fn read_all(string: &str) -> Result<Vec<MalValue>, MalError> {
+    let mut reader = MalReader::new(string);
+    let mut forms = vec![];
+    while reader.peek().is_some() {
+        forms.push(reader.read_form()?);
+    }
+    Ok(forms)
+}
+
 struct MalReader {
     tokens: Vec<String>,
+    /// The location of each token in the input string.
+    spans: Vec<Span>,
     position: usize,
+    /// The location of the last token read.
+    span: Span,
+    /// The length of the input string.
+    end: usize,
+    /// The input string.
+    text: Rc<str>,
 }
 
 impl MalReader {
+    fn new(string: &str) -> MalReader {
+        let (tokens, spans) = tokenize(string);
+        MalReader {
+            tokens,
+            spans,
+            position: 0,
+            span: Span { start: 0, end: 0 },
+            end: string.len(),
+            text: string.into(),
+        }
+    }
+
+    /// Read the form at the current position, spanning a reader error.
+    fn read_form(&mut self) -> MalResult {
+        read_form(self).map_err(|why| match why.span {
+            None if why.kind == MalErrorKind::Reader => why.with_span(self.span),
+            _ => why,
+        })
+    }
+
     /// Get the token at the current position then increment the position.
     pub fn next(&mut self) -> Option<&str> {
         let token = if self.position < self.tokens.len() {
+            self.span = self.spans[self.position];
             Some(&self.tokens[self.position][..])
         } else {
+            self.span = Span {
+                start: self.end,
+                end: self.end,
+            };
             None
         };
         self.position += 1;
@@ -45,38 +94,44 @@ impl MalReader {
     }
 }
 
-const MATCH_TOKEN_PCRE: &str =
-    r#"[\s,]*(~@|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"|;.*|[^\s\[\]{}('"`,;)]*)"#;
+const MATCH_TOKEN_PCRE: &str = r#"[\s,]*(~@|#\{|#"(?:\\.|[^\\"])*"|[\[\]{}()'`~^@]|"(?:\\.|[^\\"])*"?|;.*|\\.[^\s\[\]{}('"`,;)]*|[^\s\[\]{}('"`,;)]*)"#;
 const MATCH_INTEGER_LITERAL_PCRE: &str = r#"[+-]?\b[0-9]+\b"#;
 const MATCH_STRING_LITERAL_PCRE: &str = r#""(?:\\.|[^\\"])*""#;
 
-/// Construct the 'Vec' containing the tokens in the given string.
-fn tokenize(string: &str) -> Vec<String> {
-    let mut tokens = vec![];
+/// Construct the 'Vec' containing the tokens in the given string, and the
+/// 'Vec' of their locations.
+fn tokenize(string: &str) -> (Vec<String>, Vec<Span>) {
+    let (mut tokens, mut spans) = (vec![], vec![]);
     let re = mal_regex!(MATCH_TOKEN_PCRE);
     for caps in re.captures_iter(string) {
-        let group = &caps[1];
-        if group == "" {
+        let group = caps.get(1).unwrap();
+        if group.as_str().is_empty() {
             break;
         }
-        if group.starts_with(';') {
+        if group.as_str().starts_with(';') {
             continue;
         }
-        tokens.push(group.to_string());
+        tokens.push(group.as_str().to_string());
+        spans.push(Span {
+            start: group.start(),
+            end: group.end(),
+        });
     }
-    tokens
+    (tokens, spans)
 }
 
 /// Try to construct the 'MalValue' corresponding to the current token stored
 /// in the given 'MalReader'.
 fn read_form(reader: &mut MalReader) -> MalResult {
     match reader.peek().unwrap() {
-        ")" => err_str("unexpected ')'"),
+        ")" | "]" | "}" => {
+            let token = reader.next().unwrap();
+            err_string(MalErrorKind::Reader, format!("unexpected '{}'", token))
+        }
         "(" => read_list(reader),
-        "]" => err_str("unexpected ']'"),
         "[" => read_vector(reader),
-        "}" => err_str("unexpected '}'"),
         "{" => read_hash(reader),
+        "#{" => read_set(reader),
         _ => read_atom(reader),
     }
 }
@@ -85,7 +140,7 @@ fn read_form(reader: &mut MalReader) -> MalResult {
 fn read_atom(reader: &mut MalReader) -> MalResult {
     let token = match reader.next() {
         Some(token) => token,
-        None => return err_str("read_atom underflow"),
+        None => return err_str(MalErrorKind::Reader, "read_atom underflow"),
     };
     if token == "nil" {
         Ok(types::new_nil())
@@ -93,14 +148,87 @@ fn read_atom(reader: &mut MalReader) -> MalResult {
         Ok(types::new_true())
     } else if token == "false" {
         Ok(types::new_false())
-    } else if mal_regex!(MATCH_STRING_LITERAL_PCRE).is_match(token) {
-        Ok(types::new_str_from_slice(&token[1..token.len() - 1]))
+    } else if let Some(name) = token.strip_prefix('\\') {
+        read_char(name)
+    } else if token.starts_with("#\"") {
+        read_regex(&token[2..token.len() - 1])
+    } else if token.starts_with('"') {
+        if token.len() < 2 || !mal_regex!(MATCH_STRING_LITERAL_PCRE).is_match(token) {
+            return err_str(
+                MalErrorKind::Reader,
+                "unbalanced string, expected '\"', got EOF",
+            );
+        }
+        Ok(types::new_str(unescape_str(&token[1..token.len() - 1])))
     } else if mal_regex!(MATCH_INTEGER_LITERAL_PCRE).is_match(token) {
         let integer: i32 = token.parse().ok().unwrap();
         Ok(types::new_integer(integer))
+    } else if token.len() > 1 && token.starts_with(':') {
+        Ok(types::new_keyword(token[1..].to_string()))
     } else {
-        Ok(types::new_symbol(token.to_string()))
+        Ok(types::new_symbol(token))
+    }
+}
+
+/// Build the character of the given literal (without its leading '\'): either
+/// the character itself, its name (`newline`, `return`, `space` or `tab`) or
+/// its unicode escape (`uXXXX`).
+fn read_char(name: &str) -> MalResult {
+    let mut chars = name.chars();
+    let c = match (name, chars.next(), chars.next()) {
+        ("newline", _, _) => '\n',
+        ("return", _, _) => '\r',
+        ("space", _, _) => ' ',
+        ("tab", _, _) => '\t',
+        (_, Some(c), None) => c,
+        _ if name.starts_with('u') && name.len() == 5 => u32::from_str_radix(&name[1..], 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| {
+                MalError::new(
+                    MalErrorKind::Reader,
+                    format!("invalid unicode escape '\\{}'", name),
+                )
+            })?,
+        _ => {
+            return Err(MalError::new(
+                MalErrorKind::Reader,
+                format!("invalid character literal '\\{}'", name),
+            ))
+        }
+    };
+    Ok(types::new_char(c))
+}
+
+/// Compile the given regex literal content: only the `\"` escape sequence
+/// is replaced, the others are left to the regex syntax (e.g. `#"\d+"`).
+fn read_regex(literal: &str) -> MalResult {
+    match Regex::new(&literal.replace("\\\"", "\"")) {
+        Ok(regex) => Ok(types::new_regex(regex)),
+        Err(why) => Err(MalError::new(
+            MalErrorKind::Reader,
+            format!("invalid regex literal #\"{}\": {}", literal, why),
+        )),
+    }
+}
+
+/// Replace the escape sequences (`\"`, `\n` and `\\`) in the given string literal
+/// content.
+fn unescape_str(literal: &str) -> String {
+    let mut string = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some(escaped) => string.push(escaped),
+            None => string.push('\\'),
+        }
     }
+    string
 }
 
 /// Read a sequence in 'MalReader' beginning and ending with the given
@@ -117,13 +245,19 @@ fn read_seq(reader: &mut MalReader, start: &str, end: &str) -> Result<Vec<MalVal
         None => Some("read_seq underflow".into()),
     };
     if let Some(error) = oerror {
-        return Err(MalError::ErrString(error));
+        return Err(MalError::new(MalErrorKind::Reader, error));
     }
     let mut seq: Vec<MalValue> = vec![];
     loop {
         match reader.peek() {
             Some(token) if token == end => break,
-            None => return Err(MalError::ErrString(format!("expected '{}', got EOF", end))),
+            None => {
+                reader.next();
+                return Err(MalError::new(
+                    MalErrorKind::Reader,
+                    format!("expected '{}', got EOF", end),
+                ));
+            }
             _ => match read_form(reader) {
                 Ok(value) => seq.push(value),
                 Err(why) => return Err(why),
@@ -135,7 +269,20 @@ fn read_seq(reader: &mut MalReader, start: &str, end: &str) -> Result<Vec<MalVal
 }
 
 fn read_list(reader: &mut MalReader) -> MalResult {
-    read_seq(reader, "(", ")").map(types::new_list)
+    let start = reader.spans[reader.position].start;
+    let seq = read_seq(reader, "(", ")")?;
+    // the fn* and defn forms keep their source, for introspection
+    let source = match seq.first().map(|head| &**head) {
+        Some(&Symbol(symbol::FN)) | Some(&Symbol(symbol::DEFN)) => Some(Rc::new(Source {
+            text: reader.text.clone(),
+            span: Span {
+                start,
+                end: reader.span.end,
+            },
+        })),
+        _ => None,
+    };
+    Ok(types::new_list_with_source(seq, source))
 }
 
 fn read_vector(reader: &mut MalReader) -> MalResult {
@@ -147,15 +294,23 @@ fn read_hash(reader: &mut MalReader) -> MalResult {
     let seq = read_seq(reader, "{", "}")?;
     let mut iter = seq.iter();
     while let Some(key) = iter.next() {
-        let k = match **key {
-            types::MalType::Str(ref string) => string.clone(),
-            _ => return err_str("expected str key for hash map"),
-        };
         let v = match iter.next() {
             Some(next) => next,
-            None => return err_str("unbalanced hash map (key with no value)"),
+            None => {
+                return err_str(
+                    MalErrorKind::Reader,
+                    "unbalanced hash map (key with no value)",
+                )
+            }
         };
-        map.insert(k, v.clone());
+        map.insert(types::MalHashKey::new(key.clone()), v.clone());
     }
     Ok(types::new_hash(map))
 }
+
+fn read_set(reader: &mut MalReader) -> MalResult {
+    let seq = read_seq(reader, "#{", "}")?;
+    Ok(types::new_set(
+        seq.into_iter().map(types::MalHashKey::new).collect(),
+    ))
+}
@@ -0,0 +1,193 @@
+/// Module resolving the symbols of a function body to their lexical address,
+/// so that evaluating them skips the hash lookups in the environments they
+/// are not bound in (see 'env::lookup_at').
+///
+/// Only the symbols bound within the function itself (its parameters, those
+/// of the nested functions and the let* bindings) are resolved: the free
+/// ones are left to the dynamic lookup, since they can be defined at any
+/// time. A body containing def! is left as is, since def! can bind a symbol
+/// in an environment shadowing its resolved one.
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use super::destructure;
+use super::eval;
+use super::symbol::{self, SymbolId};
+use super::types;
+use super::types::MalType::*;
+use super::types::{
+    new_hash, new_list, new_list_with_source, new_set, new_vector, MalHashContainer, MalHashKey,
+    MalSetContainer, MalValue,
+};
+
+/// The symbols bound by each environment, from the outermost one.
+type Scopes = Vec<HashSet<SymbolId>>;
+
+/// Return the symbols bound by the given binding pattern (none if it is
+/// malformed, its evaluation failing anyway).
+fn pattern_symbols(pattern: &MalValue) -> Vec<SymbolId> {
+    destructure::parse(pattern)
+        .map(|pattern| pattern.symbols())
+        .unwrap_or_default()
+}
+
+/// Return the symbols bound by the given parameters list.
+fn params_scope(params: &MalValue) -> HashSet<SymbolId> {
+    let mut scope = HashSet::new();
+    if let List(ref seq, _) | Vector(ref seq) = **params {
+        for param in seq {
+            scope.extend(pattern_symbols(param));
+        }
+    }
+    scope
+}
+
+/// Return true if the given AST contains a def! (or defn) form.
+fn defines(ast: &MalValue) -> bool {
+    match **ast {
+        List(ref seq, _) => {
+            matches!(
+                seq.front().map(|head| &**head),
+                Some(&Symbol(symbol::DEF)) | Some(&Symbol(symbol::DEFN))
+            ) || seq.iter().any(defines)
+        }
+        Vector(ref seq) => seq.iter().any(defines),
+        Hash(ref hash) => hash.values().any(defines),
+        Set(ref set) => set.iter().any(|item| defines(item.value())),
+        _ => false,
+    }
+}
+
+/// Return true if the given AST contains a resolved symbol, i.e. if it is the
+/// body of a function nested in one already resolved (the resolution of a
+/// function body including its nested functions ones).
+fn resolved(ast: &MalValue) -> bool {
+    match **ast {
+        LocalSymbol(_, _) => true,
+        List(ref seq, _) | Vector(ref seq) => seq.iter().any(resolved),
+        Hash(ref hash) => hash.values().any(resolved),
+        Set(ref set) => set.iter().any(|item| resolved(item.value())),
+        _ => false,
+    }
+}
+
+/// Return the body of the function with the given parameters, with its
+/// symbols resolved to their lexical address.
+///
+/// The body of a function nested in a resolved one is returned as is, so
+/// that creating a closure does not resolve its body again.
+pub fn resolve_fn(params: &MalValue, body: &MalValue) -> MalValue {
+    if resolved(body) || defines(body) {
+        return body.clone();
+    }
+    let mut scopes = vec![params_scope(params)];
+    resolve(body, &mut scopes)
+}
+
+/// Resolve the given body of a function with the given parameters.
+fn resolve_body(params: &MalValue, body: &MalValue, scopes: &mut Scopes) -> MalValue {
+    scopes.push(params_scope(params));
+    let body = resolve(body, scopes);
+    scopes.pop();
+    body
+}
+
+fn resolve(ast: &MalValue, scopes: &mut Scopes) -> MalValue {
+    match **ast {
+        Symbol(name) => match scopes.iter().rev().position(|scope| scope.contains(&name)) {
+            Some(depth) => Rc::new(LocalSymbol(name, depth)),
+            None => ast.clone(),
+        },
+        List(ref seq, _) => {
+            let head = match seq.front().map(|head| &**head) {
+                Some(&Symbol(head)) => Some(head),
+                _ => None,
+            };
+            match head {
+                // (let* (key0 value0 ...) body) or (loop (key0 value0 ...)
+                // body) : all the keys are bound in the same new environment
+                Some(symbol::LET) | Some(symbol::LOOP) if seq.len() == 3 => match *seq[1] {
+                    List(ref bindings, _) | Vector(ref bindings) => {
+                        let mut scope = HashSet::new();
+                        for key in bindings.iter().step_by(2) {
+                            scope.extend(pattern_symbols(key));
+                        }
+                        scopes.push(scope);
+                        let bindings = bindings
+                            .iter()
+                            .enumerate()
+                            .map(|(i, item)| {
+                                if i % 2 == 0 {
+                                    item.clone()
+                                } else {
+                                    resolve(item, scopes)
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        let body = resolve(&seq[2], scopes);
+                        scopes.pop();
+                        new_list(vec![seq[0].clone(), new_list(bindings), body])
+                    }
+                    _ => ast.clone(),
+                },
+                // (fn* (params...) body) : the parameters are bound in a new
+                // environment, as the ones of each arity of (fn* ((params...)
+                // body)...)
+                Some(symbol::FN) if eval::is_multi_arity(seq) => {
+                    let mut resolved = vec![seq[0].clone()];
+                    for clause in seq.iter().skip(1) {
+                        resolved.push(match **clause {
+                            List(ref clause, _) if clause.len() == 2 => new_list(vec![
+                                clause[0].clone(),
+                                resolve_body(&clause[0], &clause[1], scopes),
+                            ]),
+                            _ => clause.clone(),
+                        });
+                    }
+                    new_list_with_source(resolved, types::source_of(ast))
+                }
+                Some(symbol::FN) if seq.len() == 3 => {
+                    let body = resolve_body(&seq[1], &seq[2], scopes);
+                    new_list_with_source(
+                        vec![seq[0].clone(), seq[1].clone(), body],
+                        types::source_of(ast),
+                    )
+                }
+                // the introspection and namespaces special forms take
+                // unevaluated symbols
+                Some(symbol::UNDEF)
+                | Some(symbol::RESOLVE)
+                | Some(symbol::BOUND)
+                | Some(symbol::NS)
+                | Some(symbol::REQUIRE) => ast.clone(),
+                // the other special forms only evaluate their arguments
+                Some(symbol::DO) | Some(symbol::IF) | Some(symbol::LAZY_SEQ) => {
+                    let mut resolved = vec![seq[0].clone()];
+                    resolved.extend(seq.iter().skip(1).map(|item| resolve(item, scopes)));
+                    new_list(resolved)
+                }
+                _ => new_list(
+                    seq.iter()
+                        .map(|item| resolve(item, scopes))
+                        .collect::<Vec<_>>(),
+                ),
+            }
+        }
+        Vector(ref seq) => new_vector(
+            seq.iter()
+                .map(|item| resolve(item, scopes))
+                .collect::<Vec<_>>(),
+        ),
+        Hash(ref hash) => new_hash(
+            hash.iter()
+                .map(|(key, value)| (key.clone(), resolve(value, scopes)))
+                .collect::<MalHashContainer>(),
+        ),
+        Set(ref set) => new_set(
+            set.iter()
+                .map(|item| MalHashKey::new(resolve(item.value(), scopes)))
+                .collect::<MalSetContainer>(),
+        ),
+        _ => ast.clone(),
+    }
+}
@@ -0,0 +1,163 @@
+/// Module defining the sequence abstraction over the MAL collections: nil,
+/// lists, vectors, hash maps (as [key value] vectors), sets, strings (as
+/// characters) and lazy sequences can all be walked through their
+/// first item and the rest.
+use std::cmp::Ordering;
+
+use super::limits;
+use super::types;
+use super::types::MalType::*;
+use super::types::{MalError, MalErrorKind, MalList, MalResult, MalType, MalValue};
+
+/// Return the items of a non-lazy seqable value, None if it is lazy.
+fn eager_items(value: &MalType) -> Result<Option<MalList>, MalError> {
+    Ok(Some(match *value {
+        Nil => MalList::new(),
+        List(ref seq, _) | Vector(ref seq) => seq.clone(),
+        Hash(ref hash) => hash
+            .iter()
+            .map(|(k, v)| types::new_vector(vec![k.value().clone(), v.clone()]))
+            .collect(),
+        Set(ref set) => set.iter().map(|k| k.value().clone()).collect(),
+        Str(ref string) => string.chars().map(types::new_char).collect(),
+        LazySeq(_) => return Ok(None),
+        _ => {
+            return Err(MalError::new(
+                MalErrorKind::Type,
+                format!("{} cannot be used as a sequence", value.pr_str(true)),
+            ))
+        }
+    }))
+}
+
+/// Return the first item and the rest of the given seqable value, or None if
+/// it is empty.
+pub fn first_rest(value: &MalValue) -> Result<Option<(MalValue, MalValue)>, MalError> {
+    match **value {
+        LazySeq(ref data) => data.realize(),
+        _ => {
+            let items = eager_items(value)?.unwrap();
+            Ok(items
+                .front()
+                .map(|first| (first.clone(), types::new_list(items.skip(1)))))
+        }
+    }
+}
+
+/// Return the given value as a sequence: nil if empty, otherwise a list or a
+/// (realized) lazy sequence.
+pub fn seq(value: &MalValue) -> MalResult {
+    match **value {
+        LazySeq(ref data) => Ok(match data.realize()? {
+            Some(_) => value.clone(),
+            None => types::new_nil(),
+        }),
+        List(ref seq, _) if !seq.is_empty() => Ok(value.clone()),
+        _ => {
+            let items = eager_items(value)?.unwrap();
+            Ok(if items.is_empty() {
+                types::new_nil()
+            } else {
+                types::new_list(items)
+            })
+        }
+    }
+}
+
+/// Iterator over the items of a seqable value, realizing lazy sequences as
+/// needed.
+pub struct SeqIter {
+    rest: Option<MalValue>,
+}
+
+impl Iterator for SeqIter {
+    type Item = MalResult;
+
+    fn next(&mut self) -> Option<MalResult> {
+        let rest = self.rest.take()?;
+        match first_rest(&rest) {
+            Ok(Some((first, rest))) => {
+                self.rest = Some(rest);
+                Some(Ok(first))
+            }
+            Ok(None) => None,
+            Err(why) => Some(Err(why)),
+        }
+    }
+}
+
+/// Return an iterator over the items of the given seqable value.
+pub fn iter(value: &MalValue) -> Result<SeqIter, MalError> {
+    let rest = match **value {
+        LazySeq(_) | List(..) => value.clone(),
+        _ => types::new_list(eager_items(value)?.unwrap()),
+    };
+    Ok(SeqIter { rest: Some(rest) })
+}
+
+/// Return an iterator over the items of the given seqable value, when only
+/// borrowed.
+fn iter_borrowed(value: &MalType) -> Result<SeqIter, MalError> {
+    let rest = match *value {
+        LazySeq(ref data) => match data.realize()? {
+            Some((first, rest)) => types::new_cons(first, rest),
+            None => types::new_nil(),
+        },
+        _ => types::new_list(eager_items(value)?.unwrap()),
+    };
+    Ok(SeqIter { rest: Some(rest) })
+}
+
+/// Return true if the given seqable values have equal items, only realizing
+/// them up to the first difference (so that a finite sequence can be
+/// compared with an infinite one).
+pub fn equal(a: &MalType, b: &MalType) -> Result<bool, MalError> {
+    let (mut a, mut b) = (iter_borrowed(a)?, iter_borrowed(b)?);
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ok(true),
+            (Some(a), Some(b)) => {
+                if a? != b? {
+                    return Ok(false);
+                }
+            }
+            _ => return Ok(false),
+        }
+    }
+}
+
+/// Compare the items of the given seqable values, only realizing them up to
+/// the first difference.
+pub fn compare(a: &MalType, b: &MalType) -> Result<Ordering, MalError> {
+    let (mut a, mut b) = (iter_borrowed(a)?, iter_borrowed(b)?);
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ok(Ordering::Equal),
+            (None, Some(_)) => return Ok(Ordering::Less),
+            (Some(_), None) => return Ok(Ordering::Greater),
+            (Some(a), Some(b)) => match types::try_compare_values(&a?, &b?)? {
+                Ordering::Equal => (),
+                ordering => return Ok(ordering),
+            },
+        }
+    }
+}
+
+/// Return all the items of the given seqable value, fully realizing it if
+/// lazy (and thus only returning for an infinite sequence once an evaluation
+/// limit is exceeded, see 'limits').
+pub fn to_vec(value: &MalType) -> Result<Vec<MalValue>, MalError> {
+    match *value {
+        LazySeq(ref data) => {
+            let mut items = vec![];
+            let mut realized = data.realize()?;
+            while let Some((first, rest)) = realized {
+                limits::check_size(items.len() + 1)?;
+                items.push(first);
+                realized = first_rest(&rest)?;
+            }
+            Ok(items)
+        }
+        _ => Ok(eager_items(value)?.unwrap().into_iter().collect()),
+    }
+}
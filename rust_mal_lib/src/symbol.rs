@@ -0,0 +1,134 @@
+/// Module interning the MAL symbols: each symbol name is stored once in a
+/// (per thread) table and a symbol is only its index in it, so that symbols
+/// are cheap to clone, compare and hash.
+///
+/// The special forms are interned first, in the order of their constants, so
+/// that they can be dispatched on without any lookup.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::rc::Rc;
+
+/// An interned symbol.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(u32);
+
+pub const DEF: SymbolId = SymbolId(0);
+pub const LET: SymbolId = SymbolId(1);
+pub const DO: SymbolId = SymbolId(2);
+pub const IF: SymbolId = SymbolId(3);
+pub const FN: SymbolId = SymbolId(4);
+pub const LAZY_SEQ: SymbolId = SymbolId(5);
+/// The '&' separating the variadic parameter of a function.
+pub const AMPERSAND: SymbolId = SymbolId(6);
+pub const UNDEF: SymbolId = SymbolId(7);
+pub const RESOLVE: SymbolId = SymbolId(8);
+pub const BOUND: SymbolId = SymbolId(9);
+pub const NS_PUBLICS: SymbolId = SymbolId(10);
+pub const NS: SymbolId = SymbolId(11);
+pub const REQUIRE: SymbolId = SymbolId(12);
+pub const LOOP: SymbolId = SymbolId(13);
+pub const RECUR: SymbolId = SymbolId(14);
+pub const DEFN: SymbolId = SymbolId(15);
+
+/// The names of the constant symbols, in the order of their indices.
+const PREDEFINED: [&str; 16] = [
+    "def!",
+    "let*",
+    "do",
+    "if",
+    "fn*",
+    "lazy-seq",
+    "&",
+    "undef!",
+    "resolve",
+    "bound?",
+    "ns-publics",
+    "ns",
+    "require",
+    "loop",
+    "recur",
+    "defn",
+];
+
+struct SymbolTable {
+    ids: HashMap<Rc<str>, SymbolId>,
+    names: Vec<Rc<str>>,
+}
+
+impl SymbolTable {
+    fn new() -> SymbolTable {
+        let mut table = SymbolTable {
+            ids: HashMap::new(),
+            names: vec![],
+        };
+        for name in PREDEFINED.iter() {
+            table.intern(name);
+        }
+        table
+    }
+
+    fn intern(&mut self, name: &str) -> SymbolId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = SymbolId(self.names.len() as u32);
+        let name: Rc<str> = name.into();
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        id
+    }
+}
+
+thread_local! {
+    static SYMBOLS: RefCell<SymbolTable> = RefCell::new(SymbolTable::new());
+}
+
+impl SymbolId {
+    /// Return the symbol with the given name, interning it if needed.
+    pub fn intern(name: &str) -> SymbolId {
+        SYMBOLS.with(|symbols| symbols.borrow_mut().intern(name))
+    }
+
+    /// Return the name of the symbol.
+    pub fn name(self) -> Rc<str> {
+        SYMBOLS.with(|symbols| symbols.borrow().names[self.0 as usize].clone())
+    }
+}
+
+impl fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Debug for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Hasher for the symbols: their indices being already unique, they only
+/// need to be spread over the hash bits.
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ u64::from(byte)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+    }
+
+    fn write_u32(&mut self, id: u32) {
+        self.0 = u64::from(id).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+/// Hash map keyed by symbols.
+pub type SymbolMap<V> = HashMap<SymbolId, V, BuildHasherDefault<SymbolHasher>>;
@@ -1,11 +1,22 @@
-use std::collections::HashMap;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::cmp::Ordering;
 use std::fmt;
+use std::ops::Deref;
 use std::rc::Rc;
 
 use self::MalType::*;
 use super::env::Env;
+use super::limits;
+use super::seq;
+use super::symbol::{self, SymbolId};
 
-pub type MalHashContainer = HashMap<String, MalValue>;
+/// Persistent (structurally shared) containers: cloning them is O(1) and
+/// functional updates are O(log n).
+///
+/// Hash maps and sets are ordered by key, so that they print deterministically.
+pub type MalList = im_rc::Vector<MalValue>;
+pub type MalHashContainer = im_rc::OrdMap<MalHashKey, MalValue>;
+pub type MalSetContainer = im_rc::OrdSet<MalHashKey>;
 
 /// The different types a MAL value can take.
 #[allow(non_camel_case_types)]
@@ -15,10 +26,26 @@ pub enum MalType {
     False,
     Integer(i32),
     Str(String),
-    Symbol(String),
-    List(Vec<MalValue>),
-    Vector(Vec<MalValue>),
+    Char(char),
+    Symbol(SymbolId),
+    /// A symbol of a function body resolved to its lexical address: bound
+    /// in the given number of environments up from the evaluating one (see
+    /// 'resolve'). Otherwise the same as the plain symbol.
+    LocalSymbol(SymbolId, usize),
+    Keyword(String),
+    /// A list, with where it was read if it is a fn* or defn form read by
+    /// 'reader' (kept by the forms rewritten from it, for introspection).
+    List(MalList, Option<Rc<Source>>),
+    Vector(MalList),
     Hash(MalHashContainer),
+    Set(MalSetContainer),
+    /// A (possibly infinite) sequence, realized on demand.
+    LazySeq(LazySeqData),
+    /// A tagged literal (e.g. `#inst "1985-04-12T23:20:50.52Z"`), kept as its
+    /// tag (without the '#') and its value.
+    Tagged(String, MalValue),
+    /// A compiled regular expression (e.g. `#"[0-9]+"`).
+    Regex(MalRegex),
     /// A native function, implemented in the host language (i.e. in Rust).
     Function(FunctionData<'static>),
     /// A lambda function, defined in Make A Lisp.
@@ -31,19 +58,22 @@ impl MalType {
         match *self {
             Function(ref data) => {
                 if data.arity.is_some() && args.len() != data.arity.unwrap() {
-                    err_string(format!("wrong arity ({}) for {:?}", args.len(), data))
+                    err_string(
+                        MalErrorKind::Arity,
+                        format!("wrong arity ({}) for {:?}", args.len(), data),
+                    )
                 } else {
                     (data.function)(args)
                 }
             }
             MalFunction(ref data) => {
-                let exprs = new_list(args);
-                match super::env::bind(&data.env, data.args.clone(), exprs) {
-                    Ok(eval_env) => (data.eval)(data.exp.clone(), eval_env),
-                    Err(why) => err_string(why),
-                }
+                let (exp, eval_env) = data.bind(args)?;
+                (data.eval)(exp, eval_env).map_err(|why| why.with_frame(data.frame_name()))
             }
-            _ => err_str("cannot call a non-function"),
+            _ => err_string(
+                MalErrorKind::Type,
+                format!("cannot call the non-function {}", self.pr_str(true)),
+            ),
         }
     }
 }
@@ -55,11 +85,22 @@ impl PartialEq for MalType {
             (&True, &True) => true,
             (&False, &False) => true,
             (&Integer(a), &Integer(b)) => a == b,
-            (&Str(ref a), &Str(ref b)) => a == b,
-            (&Symbol(ref a), &Symbol(ref b)) => a == b,
-            (&List(ref a), &List(ref b)) => a == b,
-            (&Vector(ref a), &Vector(ref b)) => a == b,
-            (&Hash(ref a), &Hash(ref b)) => a == b,
+            (Str(a), Str(b)) => a == b,
+            (&Char(a), &Char(b)) => a == b,
+            (Symbol(a), Symbol(b))
+            | (Symbol(a), LocalSymbol(b, _))
+            | (LocalSymbol(a, _), Symbol(b))
+            | (LocalSymbol(a, _), LocalSymbol(b, _)) => a == b,
+            (Keyword(a), Keyword(b)) => a == b,
+            (List(a, _), List(b, _)) => a == b,
+            (Vector(a), Vector(b)) => a == b,
+            (Hash(a), Hash(b)) => a == b,
+            (Set(a), Set(b)) => a == b,
+            (Tagged(tag_a, a), Tagged(tag_b, b)) => tag_a == tag_b && a == b,
+            (Regex(a), Regex(b)) => a.as_str() == b.as_str(),
+            (LazySeq(_), List(..)) | (List(..), LazySeq(_)) | (LazySeq(_), LazySeq(_)) => {
+                seq::equal(self, other).unwrap_or(false)
+            }
             (&Function(_), &Function(_)) => {
                 warn!("cannot compare two functions");
                 false
@@ -76,6 +117,171 @@ impl PartialEq for MalType {
     }
 }
 
+/// A MAL value used as a hash map key or as a set element.
+///
+/// Wraps the value with the total ordering (and the consistent equality)
+/// required by the containers: values are ordered by type then by content,
+/// functions are only equal to themselves.
+#[derive(Clone)]
+pub struct MalHashKey(MalValue);
+
+impl MalHashKey {
+    pub fn new(value: MalValue) -> MalHashKey {
+        MalHashKey(value)
+    }
+
+    pub fn value(&self) -> &MalValue {
+        &self.0
+    }
+}
+
+impl PartialEq for MalHashKey {
+    fn eq(&self, other: &MalHashKey) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MalHashKey {}
+
+impl PartialOrd for MalHashKey {
+    fn partial_cmp(&self, other: &MalHashKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MalHashKey {
+    fn cmp(&self, other: &MalHashKey) -> Ordering {
+        compare_values(&self.0, &other.0)
+    }
+}
+
+/// Rank of the type of the given value in the total ordering of MAL values.
+fn type_rank(value: &MalType) -> u8 {
+    match *value {
+        Nil => 0,
+        False => 1,
+        True => 2,
+        Integer(_) => 3,
+        Char(_) => 4,
+        Str(_) => 5,
+        Keyword(_) => 6,
+        Symbol(_) | LocalSymbol(_, _) => 7,
+        List(..) | LazySeq(_) => 8,
+        Vector(_) => 9,
+        Hash(_) => 10,
+        Set(_) => 11,
+        Tagged(_, _) => 12,
+        Regex(_) => 13,
+        Function(_) => 14,
+        MalFunction(_) => 15,
+    }
+}
+
+/// Compare two MAL values with the total ordering used by the sorted
+/// containers (by type, then by content). The comparison cannot fail there:
+/// values whose comparison fails (on a lazy sequence failing to realize) are
+/// considered equal, see 'try_compare_values' to get the error.
+pub fn compare_values(a: &MalValue, b: &MalValue) -> Ordering {
+    try_compare_values(a, b).unwrap_or(Ordering::Equal)
+}
+
+/// Compare two MAL values with the total ordering of 'compare_values',
+/// failing if a lazy sequence fails to realize.
+pub fn try_compare_values(a: &MalValue, b: &MalValue) -> Result<Ordering, MalError> {
+    fn compare_seqs<'a, I, J>(mut a: I, mut b: J) -> Result<Ordering, MalError>
+    where
+        I: Iterator<Item = &'a MalValue>,
+        J: Iterator<Item = &'a MalValue>,
+    {
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return Ok(Ordering::Equal),
+                (None, Some(_)) => return Ok(Ordering::Less),
+                (Some(_), None) => return Ok(Ordering::Greater),
+                (Some(a), Some(b)) => match try_compare_values(a, b)? {
+                    Ordering::Equal => (),
+                    ordering => return Ok(ordering),
+                },
+            }
+        }
+    }
+
+    Ok(match (&**a, &**b) {
+        (&Integer(a), &Integer(b)) => a.cmp(&b),
+        (&Char(a), &Char(b)) => a.cmp(&b),
+        (Str(a), Str(b)) | (Keyword(a), Keyword(b)) => a.cmp(b),
+        (Symbol(a), Symbol(b))
+        | (Symbol(a), LocalSymbol(b, _))
+        | (LocalSymbol(a, _), Symbol(b))
+        | (LocalSymbol(a, _), LocalSymbol(b, _)) => a.name().cmp(&b.name()),
+        (List(a, _), List(b, _)) | (Vector(a), Vector(b)) => compare_seqs(a.iter(), b.iter())?,
+        (LazySeq(_), List(..)) | (List(..), LazySeq(_)) | (LazySeq(_), LazySeq(_)) => {
+            seq::compare(a, b)?
+        }
+        (Hash(a), Hash(b)) => compare_seqs(
+            a.iter().flat_map(|(k, v)| vec![k.value(), v]),
+            b.iter().flat_map(|(k, v)| vec![k.value(), v]),
+        )?,
+        (Set(a), Set(b)) => compare_seqs(a.iter().map(|k| k.value()), b.iter().map(|k| k.value()))?,
+        (Tagged(tag_a, a), Tagged(tag_b, b)) => match tag_a.cmp(tag_b) {
+            Ordering::Equal => try_compare_values(a, b)?,
+            ordering => ordering,
+        },
+        (Regex(a), Regex(b)) => a.as_str().cmp(b.as_str()),
+        (Function(a), Function(b)) => a.name.cmp(b.name),
+        // by name and source, the oldest first
+        (MalFunction(a), MalFunction(b)) => a
+            .name()
+            .cmp(&b.name())
+            .then_with(|| {
+                a.source()
+                    .map(Source::as_str)
+                    .cmp(&b.source().map(Source::as_str))
+            })
+            .then(a.id.cmp(&b.id)),
+        (a, b) => type_rank(a).cmp(&type_rank(b)),
+    })
+}
+
+/// A compiled regular expression, along with its form anchored on the whole
+/// input, compiled on first use (by re-matches).
+#[derive(Clone, Debug)]
+pub struct MalRegex {
+    regex: regex::Regex,
+    anchored: OnceCell<regex::Regex>,
+}
+
+impl MalRegex {
+    pub fn new(regex: regex::Regex) -> MalRegex {
+        MalRegex {
+            regex,
+            anchored: OnceCell::new(),
+        }
+    }
+
+    /// Return the regex anchored on the whole input (`^(?:regex)$`).
+    pub fn anchored(&self) -> Result<&regex::Regex, regex::Error> {
+        if let Some(anchored) = self.anchored.get() {
+            return Ok(anchored);
+        }
+        let anchored = anchor_regex(&self.regex)?;
+        Ok(self.anchored.get_or_init(|| anchored))
+    }
+}
+
+impl Deref for MalRegex {
+    type Target = regex::Regex;
+
+    fn deref(&self) -> &regex::Regex {
+        &self.regex
+    }
+}
+
+/// Return the given regex anchored on the whole input (`^(?:regex)$`).
+pub fn anchor_regex(regex: &regex::Regex) -> Result<regex::Regex, regex::Error> {
+    regex::Regex::new(&format!("^(?:{})$", regex.as_str()))
+}
+
 /// Metadata for a native Rust function operating on MAL values.
 pub struct FunctionData<'a> {
     /// The Rust evaluating function.
@@ -104,40 +310,452 @@ impl<'a> fmt::Debug for FunctionData<'a> {
     }
 }
 
+/// The number of arguments a MAL function takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity {
+    /// The number of required arguments.
+    pub required: usize,
+    /// Whether any number of additional arguments is accepted (with '&').
+    pub variadic: bool,
+}
+
+impl Arity {
+    /// Return the arity of a function with the given parameters list.
+    pub fn of_params(params: &MalValue) -> Arity {
+        let names = match **params {
+            List(ref seq, _) | Vector(ref seq) => seq.clone(),
+            _ => MalList::new(),
+        };
+        match names
+            .iter()
+            .position(|param| matches!(**param, Symbol(symbol::AMPERSAND)))
+        {
+            Some(required) => Arity {
+                required,
+                variadic: true,
+            },
+            None => Arity {
+                required: names.len(),
+                variadic: false,
+            },
+        }
+    }
+
+    /// Check that a function of this arity accepts the given number of
+    /// arguments.
+    pub fn check(&self, count: usize) -> Result<(), MalError> {
+        if count < self.required {
+            Err(MalError::new(
+                MalErrorKind::Arity,
+                "not enough parameters for binding",
+            ))
+        } else if count > self.required && !self.variadic {
+            Err(MalError::new(
+                MalErrorKind::Arity,
+                "too many parameters for binding",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.required,
+            if self.variadic { "+" } else { "" }
+        )
+    }
+}
+
+/// The location of a form in the source text it was read from.
+#[derive(Clone, Debug)]
+pub struct Source {
+    /// The whole text read.
+    pub text: Rc<str>,
+    /// The location of the form in the text.
+    pub span: Span,
+}
+
+impl Source {
+    /// Return the text of the form.
+    pub fn as_str(&self) -> &str {
+        &self.text[self.span.start..self.span.end]
+    }
+}
+
+/// How the parameters of a MAL function are bound to its arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// By name, in a new environment (the functions of 'eval' and 'vm').
+    Named,
+    /// By position, in the slots of a new environment (the functions of
+    /// 'analyzer', whose body can only be evaluated by it).
+    Slots,
+}
+
+/// One arity of a function defined in MAL: its parameters and body.
+pub struct FnClause {
+    /// The parameters (list of symbols or binding patterns).
+    params: MalValue,
+    body: MalValue,
+    /// The number of arguments of the clause.
+    arity: Arity,
+}
+
 /// Metadata for a function defined in MAL (a lambda).
+#[derive(Clone)]
 pub struct MalFunctionData {
     /// The Rust function used to evaluate the function body.
     eval: fn(MalValue, Env) -> MalResult,
     /// The function outer environment.
     env: Env,
-    /// The function parameters (list of symbols).
-    args: MalValue,
-    /// The function body.
-    exp: MalValue,
+    /// The arities of the function, only one unless defined with several
+    /// (e.g. `(fn* ([x] x) ([x y] y))`).
+    clauses: Rc<Vec<FnClause>>,
+    /// The name the function was first defined (by def!) or bound (by let*)
+    /// with, if any.
+    name: Option<String>,
+    /// The docstring given to def! when defining the function, if any.
+    doc: Option<String>,
+    /// Where the fn* (or defn) form defining the function was read, if known.
+    source: Option<Rc<Source>>,
+    binding: Binding,
+    /// The creation number of the function, ordering the functions of the
+    /// same name and source (see 'compare_values').
+    id: u64,
+}
+
+thread_local! {
+    /// The number of MAL functions created so far.
+    static FUNCTIONS_CREATED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Return the creation number of a new MAL function.
+fn next_function_id() -> u64 {
+    FUNCTIONS_CREATED.with(|created| {
+        created.set(created.get() + 1);
+        created.get()
+    })
+}
+
+impl MalFunctionData {
+    /// Return the function outer environment.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Bind the parameters of the function arity accepting the given
+    /// arguments to them, and return its body with the environment to
+    /// evaluate it in.
+    pub fn bind(&self, args: Vec<MalValue>) -> Result<(MalValue, Env), MalError> {
+        let clause = self.clause(args.len())?;
+        let eval_env = match self.binding {
+            Binding::Named => {
+                super::env::bind(&self.env, clause.params.clone(), new_list(args), self.eval)?
+            }
+            Binding::Slots => super::env::bind_slots(&self.env, clause.arity, args)?,
+        };
+        Ok((clause.body.clone(), eval_env))
+    }
+
+    /// Return the clause of the function for the given number of arguments:
+    /// the fixed arity one taking as many if any, otherwise the variadic one
+    /// taking at most as many. A single arity function has no other one
+    /// (its binding checking the number of arguments).
+    fn clause(&self, count: usize) -> Result<&FnClause, MalError> {
+        if self.clauses.len() == 1 {
+            return Ok(&self.clauses[0]);
+        }
+        self.clauses
+            .iter()
+            .find(|clause| !clause.arity.variadic && clause.arity.required == count)
+            .or_else(|| {
+                self.clauses
+                    .iter()
+                    .find(|clause| clause.arity.variadic && clause.arity.required <= count)
+            })
+            .ok_or_else(|| {
+                let mut arities = self
+                    .arities()
+                    .map(|arity| arity.to_string())
+                    .collect::<Vec<_>>();
+                let last = arities.pop().unwrap();
+                MalError::new(
+                    MalErrorKind::Arity,
+                    format!(
+                        "wrong arity ({}) for {}, should be {} or {}",
+                        count,
+                        self.frame_name(),
+                        arities.join(", "),
+                        last
+                    ),
+                )
+            })
+    }
+
+    /// Return whether the function binds its parameters by name, i.e. if its
+    /// body can be evaluated by any backend (rather than only by its own).
+    pub fn binds_by_name(&self) -> bool {
+        self.binding == Binding::Named
+    }
+
+    /// Return the name of the function in the error call-stack frames.
+    pub fn frame_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("fn*")
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    /// Return the parameters lists of the arities of the function.
+    pub fn arglists(&self) -> impl Iterator<Item = &MalValue> {
+        self.clauses.iter().map(|clause| &clause.params)
+    }
+
+    pub fn arities(&self) -> impl Iterator<Item = Arity> + '_ {
+        self.clauses.iter().map(|clause| clause.arity)
+    }
+
+    pub fn source(&self) -> Option<&Source> {
+        self.source.as_deref()
+    }
+}
+
+/// Return the given value named as given if it is a yet unnamed MAL function
+/// (and documented with the given docstring, if any), the value itself
+/// otherwise.
+pub fn name_function(value: MalValue, name: &str, doc: Option<String>) -> MalValue {
+    match *value {
+        MalFunction(ref data) if data.name.is_none() || doc.is_some() => {
+            let mut data = data.clone();
+            data.name = data.name.or_else(|| Some(name.to_string()));
+            data.doc = doc.or(data.doc);
+            data.id = next_function_id();
+            Rc::new(MalFunction(data))
+        }
+        _ => value,
+    }
 }
 
 impl fmt::Debug for MalFunctionData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "(fn* {:?} {:?})", self.args, self.exp)
+        write!(f, "#<fn*")?;
+        if let Some(ref name) = self.name {
+            write!(f, " {}", name)?;
+        }
+        for params in self.arglists() {
+            write!(f, " {:?}", params)?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// The deferred computation of a lazy sequence, returning any sequence (nil,
+/// list, vector, lazy sequence...).
+pub type LazySeqThunk = Box<dyn FnOnce() -> MalResult>;
+
+/// The state of a lazy sequence.
+enum LazySeqState {
+    Unrealized(LazySeqThunk),
+    Realizing,
+    /// The realization failed with the given error.
+    Failed(MalError),
+    /// Realized as the empty sequence (None), or as its first item and the
+    /// (possibly lazy) rest of the sequence.
+    Realized(Option<(MalValue, MalValue)>),
+}
+
+/// Data of a lazy sequence, realized at most once.
+pub struct LazySeqData {
+    state: RefCell<LazySeqState>,
+}
+
+impl LazySeqData {
+    /// Realize the sequence if needed, and return its first item and rest
+    /// (None if empty).
+    pub fn realize(&self) -> Result<Option<(MalValue, MalValue)>, MalError> {
+        if let LazySeqState::Unrealized(_) = *self.state.borrow() {
+            limits::step()?;
+        }
+        let state = self.state.replace(LazySeqState::Realizing);
+        let realized = match state {
+            LazySeqState::Realized(realized) => realized,
+            LazySeqState::Realizing => {
+                return Err(MalError::new(
+                    MalErrorKind::Value,
+                    "lazy sequence realized within its own realization",
+                ))
+            }
+            LazySeqState::Failed(why) => {
+                self.state.replace(LazySeqState::Failed(why.clone()));
+                return Err(why);
+            }
+            LazySeqState::Unrealized(thunk) => {
+                match thunk().and_then(|value| seq::first_rest(&value)) {
+                    Ok(realized) => realized,
+                    Err(why) => {
+                        // realizing it again would fail the same way
+                        let mut failed = why.clone();
+                        failed.message =
+                            format!("lazy sequence realization failed: {}", why.message);
+                        self.state.replace(LazySeqState::Failed(failed));
+                        return Err(why);
+                    }
+                }
+            }
+        };
+        self.state.replace(LazySeqState::Realized(realized.clone()));
+        Ok(realized)
+    }
+}
+
+impl Drop for LazySeqData {
+    /// Drop the realized items iteratively, since a long sequence would
+    /// otherwise overflow the stack with recursive drops.
+    fn drop(&mut self) {
+        let mut rest = match self.state.get_mut() {
+            LazySeqState::Realized(Some((_, ref mut rest))) => std::mem::replace(rest, new_nil()),
+            _ => return,
+        };
+        while let Ok(LazySeq(mut data)) = Rc::try_unwrap(rest) {
+            rest = match data.state.get_mut() {
+                LazySeqState::Realized(Some((_, ref mut next))) => {
+                    std::mem::replace(next, new_nil())
+                }
+                _ => return,
+            };
+        }
     }
 }
 
 /// A reference-counted MAL value.
 pub type MalValue = Rc<MalType>;
 
-#[derive(Debug)]
-pub enum MalError {
-    ErrString(String),
-    ErrEmptyLine,
+/// The category of a 'MalError'.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MalErrorKind {
+    /// Not an actual failure: the input contained no form to evaluate.
+    EmptyLine,
+    /// Malformed input text (MAL or EDN).
+    Reader,
+    /// Malformed special form (e.g. a let* binding with no value).
+    Syntax,
+    /// Function called with a wrong number of arguments.
+    Arity,
+    /// Argument of an unexpected type (e.g. calling a non-function).
+    Type,
+    /// Argument of the expected type but with an invalid value (e.g. an index
+    /// out of bounds).
+    Value,
+    /// Symbol bound in no environment.
+    UnboundSymbol,
+    DivisionByZero,
+    /// Error raised by MAL code itself.
+    User,
+    /// Evaluation limit exceeded or evaluation interrupted (see 'limits').
+    Limit,
 }
 
+impl fmt::Display for MalErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            MalErrorKind::EmptyLine => "empty line",
+            MalErrorKind::Reader => "reader",
+            MalErrorKind::Syntax => "syntax",
+            MalErrorKind::Arity => "arity",
+            MalErrorKind::Type => "type",
+            MalErrorKind::Value => "value",
+            MalErrorKind::UnboundSymbol => "unbound symbol",
+            MalErrorKind::DivisionByZero => "division by zero",
+            MalErrorKind::User => "user",
+            MalErrorKind::Limit => "limit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A range of bytes in the input text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// An error raised while reading or evaluating MAL code.
+#[derive(Clone, Debug)]
+pub struct MalError {
+    pub kind: MalErrorKind,
+    pub message: String,
+    /// Where the error occurred in the input text, if known (reader errors).
+    pub span: Option<Span>,
+    /// The path and the text of the file the span is in, if it is not the
+    /// input text (e.g. a namespace file loaded by 'require').
+    pub file: Option<(String, Rc<str>)>,
+    /// The names of the MAL functions being evaluated when the error occurred,
+    /// innermost first ("fn*" for the anonymous ones).
+    pub frames: Vec<String>,
+}
+
+impl MalError {
+    pub fn new<S: Into<String>>(kind: MalErrorKind, message: S) -> MalError {
+        MalError {
+            kind,
+            message: message.into(),
+            span: None,
+            file: None,
+            frames: vec![],
+        }
+    }
+
+    pub fn empty_line() -> MalError {
+        MalError::new(MalErrorKind::EmptyLine, "empty line")
+    }
+
+    pub fn with_span(mut self, span: Span) -> MalError {
+        self.span = Some(span);
+        self
+    }
+
+    /// Record that the span of the error (if any, and not already in a file)
+    /// is in the given file, of the given text.
+    pub fn in_file<S: Into<String>>(mut self, path: S, text: &Rc<str>) -> MalError {
+        if self.span.is_some() && self.file.is_none() {
+            self.file = Some((path.into(), text.clone()));
+        }
+        self
+    }
+
+    /// Record that the error went through the given calling frame.
+    pub fn with_frame<S: Into<String>>(mut self, frame: S) -> MalError {
+        self.frames.push(frame.into());
+        self
+    }
+}
+
+impl fmt::Display for MalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} error: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for MalError {}
+
 /// Frequently used return type for functions dealing with MAL values.
 pub type MalResult = Result<MalValue, MalError>;
-pub fn err_str(error: &str) -> MalResult {
-    Err(MalError::ErrString(error.into()))
+pub fn err_str(kind: MalErrorKind, error: &str) -> MalResult {
+    Err(MalError::new(kind, error))
 }
-pub fn err_string(error: String) -> MalResult {
-    Err(MalError::ErrString(error))
+pub fn err_string(kind: MalErrorKind, error: String) -> MalResult {
+    Err(MalError::new(kind, error))
 }
 
 pub fn new_nil() -> MalValue {
@@ -158,18 +776,57 @@ pub fn new_str(string: String) -> MalValue {
 pub fn new_str_from_slice(slice: &str) -> MalValue {
     Rc::new(Str(slice.into()))
 }
-pub fn new_symbol(symbol: String) -> MalValue {
-    Rc::new(Symbol(symbol))
+pub fn new_char(c: char) -> MalValue {
+    Rc::new(Char(c))
 }
-pub fn new_list(seq: Vec<MalValue>) -> MalValue {
-    Rc::new(List(seq))
+pub fn new_symbol(symbol: &str) -> MalValue {
+    Rc::new(Symbol(SymbolId::intern(symbol)))
 }
-pub fn new_vector(seq: Vec<MalValue>) -> MalValue {
-    Rc::new(Vector(seq))
+pub fn new_keyword(keyword: String) -> MalValue {
+    Rc::new(Keyword(keyword))
+}
+pub fn new_list<S: Into<MalList>>(seq: S) -> MalValue {
+    Rc::new(List(seq.into(), None))
+}
+/// Create a list read (or rewritten from a form read) from the given source.
+pub fn new_list_with_source<S: Into<MalList>>(seq: S, source: Option<Rc<Source>>) -> MalValue {
+    Rc::new(List(seq.into(), source))
+}
+/// Return where the given form was read, if it is a fn* or defn form read
+/// by 'reader' (or rewritten from one).
+pub fn source_of(form: &MalValue) -> Option<Rc<Source>> {
+    match **form {
+        List(_, ref source) => source.clone(),
+        _ => None,
+    }
+}
+pub fn new_vector<S: Into<MalList>>(seq: S) -> MalValue {
+    Rc::new(Vector(seq.into()))
 }
 pub fn new_hash(map: MalHashContainer) -> MalValue {
     Rc::new(Hash(map))
 }
+pub fn new_set(set: MalSetContainer) -> MalValue {
+    Rc::new(Set(set))
+}
+pub fn new_lazy_seq(thunk: LazySeqThunk) -> MalValue {
+    Rc::new(LazySeq(LazySeqData {
+        state: RefCell::new(LazySeqState::Unrealized(thunk)),
+    }))
+}
+/// Create the already realized sequence of the given first item and rest
+/// (e.g. to prepend an item to a lazy sequence).
+pub fn new_cons(first: MalValue, rest: MalValue) -> MalValue {
+    Rc::new(LazySeq(LazySeqData {
+        state: RefCell::new(LazySeqState::Realized(Some((first, rest)))),
+    }))
+}
+pub fn new_tagged(tag: String, value: MalValue) -> MalValue {
+    Rc::new(Tagged(tag, value))
+}
+pub fn new_regex(regex: regex::Regex) -> MalValue {
+    Rc::new(Regex(MalRegex::new(regex)))
+}
 pub fn new_function(
     function: fn(Vec<MalValue>) -> MalResult,
     arity: Option<usize>,
@@ -186,11 +843,52 @@ pub fn new_mal_function(
     env: Env,
     args: MalValue,
     exp: MalValue,
+    source: Option<Rc<Source>>,
+) -> MalValue {
+    new_mal_function_binding(eval, env, vec![(args, exp)], source, Binding::Named)
+}
+/// Create a MAL function of the given arities (parameters and body),
+/// binding its parameters as given.
+pub fn new_mal_function_binding(
+    eval: fn(MalValue, Env) -> MalResult,
+    env: Env,
+    clauses: Vec<(MalValue, MalValue)>,
+    source: Option<Rc<Source>>,
+    binding: Binding,
+) -> MalValue {
+    new_mal_function_clauses(eval, env, new_fn_clauses(clauses), source, binding)
+}
+/// Return the arities of a MAL function of the given parameters and bodies,
+/// to be shared by the functions created from the same form.
+pub fn new_fn_clauses(clauses: Vec<(MalValue, MalValue)>) -> Rc<Vec<FnClause>> {
+    Rc::new(
+        clauses
+            .into_iter()
+            .map(|(params, body)| FnClause {
+                arity: Arity::of_params(&params),
+                params,
+                body,
+            })
+            .collect(),
+    )
+}
+/// Create a MAL function of the given (shared) arities, binding its
+/// parameters as given.
+pub fn new_mal_function_clauses(
+    eval: fn(MalValue, Env) -> MalResult,
+    env: Env,
+    clauses: Rc<Vec<FnClause>>,
+    source: Option<Rc<Source>>,
+    binding: Binding,
 ) -> MalValue {
     Rc::new(MalFunction(MalFunctionData {
         eval,
         env,
-        args,
-        exp,
+        clauses,
+        name: None,
+        doc: None,
+        source,
+        binding,
+        id: next_function_id(),
     }))
 }
@@ -0,0 +1,608 @@
+/// Module compiling MAL forms to a compact bytecode, run by a stack-based
+/// virtual machine: an alternative evaluation backend to the tree-walking
+/// 'eval', with the same semantics.
+///
+/// Both backends share the environments and the MAL functions values, so
+/// that the functions created by one can be called by the other (e.g. by the
+/// core higher-order functions). The forms the compiler does not handle (the
+/// introspection and namespaces special forms, as well as the malformed
+/// special forms, so that they fail alike) are delegated to 'eval'.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::destructure::{self, Pattern};
+use super::env::{self, Env, Environment};
+use super::eval;
+use super::limits;
+use super::namespace;
+use super::symbol::{self, SymbolId};
+use super::types;
+use super::types::MalType::{self, *};
+use super::types::{
+    name_function, new_hash, new_lazy_seq, new_list, new_mal_function_binding, new_nil, new_set,
+    new_vector, Binding, MalError, MalErrorKind, MalHashContainer, MalHashKey, MalList, MalResult,
+    MalSetContainer, MalValue, Source,
+};
+
+/// A bytecode instruction.
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    /// Push the given constant.
+    Const(usize),
+    /// Push the value bound to the given symbol.
+    Lookup(SymbolId),
+    /// Push the value bound to the given symbol at the given lexical address.
+    LookupAt(SymbolId, usize),
+    /// Evaluate the given constant form with 'eval' and push its value.
+    Eval(usize),
+    /// Pop a value and discard it.
+    Pop,
+    /// Bind the top value to the given symbol (naming it after the symbol if
+    /// it is a function, documented with the given constant docstring if
+    /// any).
+    Def(SymbolId, Option<usize>),
+    /// Evaluate the next instructions in a new inner environment.
+    PushScope,
+    /// Return to the outer environment.
+    PopScope,
+    /// Pop a value and bind it to the given symbol in the current environment.
+    Bind(SymbolId),
+    /// Name the top value after the given symbol if it is a yet unnamed
+    /// function (bound by let*).
+    Name(SymbolId),
+    /// Pop a value and destructure it with the given binding pattern in the
+    /// current environment.
+    Destructure(usize),
+    Jump(usize),
+    /// Pop a value and jump to the given instruction if it is nil or false.
+    JumpIfFalse(usize),
+    /// Push a new function from the given prototype, closing over the current
+    /// environment.
+    Closure(usize),
+    /// Push a new lazy sequence running the given chunk in the current
+    /// environment.
+    LazySeq(usize),
+    /// Pop the given number of values and push them as a vector.
+    Vector(usize),
+    /// Pop the given number of key/value pairs and push them as a hash map.
+    Hash(usize),
+    /// Pop the given number of values and push them as a set.
+    Set(usize),
+    /// Pop the given number of arguments then the function, and call it.
+    Call(usize),
+    /// Same as 'Call', but replacing the current frame by the one of the
+    /// called function (tail call optimization).
+    TailCall(usize),
+    /// Pop the returned value of the current frame, and push it in the caller
+    /// one.
+    Return,
+}
+
+/// The definition of a function, as compiled from a fn* form.
+struct FnProto {
+    /// The parameters and body of each arity.
+    clauses: Vec<(MalValue, MalValue)>,
+    source: Option<Rc<Source>>,
+}
+
+/// How recur binds the keys of a loop to its values.
+enum LoopKeys {
+    /// One by one, the keys being all symbols.
+    Symbols(Vec<SymbolId>),
+    /// As a vector, destructured by the given pattern of the given number of
+    /// keys.
+    Pattern(usize, usize),
+}
+
+impl LoopKeys {
+    fn len(&self) -> usize {
+        match *self {
+            LoopKeys::Symbols(ref keys) => keys.len(),
+            LoopKeys::Pattern(_, count) => count,
+        }
+    }
+}
+
+/// A loop being compiled, which recur jumps back to.
+struct LoopTarget {
+    keys: LoopKeys,
+    /// The position of the first instruction of the loop body.
+    start: usize,
+    /// The number of scopes pushed when entering the loop body.
+    scopes: usize,
+}
+
+/// A compiled form (or function body).
+#[derive(Default)]
+pub struct Chunk {
+    code: Vec<Op>,
+    constants: Vec<MalValue>,
+    protos: Vec<FnProto>,
+    /// The chunks of the lazy sequences bodies.
+    chunks: Vec<Rc<Chunk>>,
+    /// The binding patterns of the let* and loop keys which are not symbols.
+    patterns: Vec<Rc<Pattern>>,
+    /// The loops being compiled, from the outermost one.
+    loops: Vec<LoopTarget>,
+    /// The number of scopes pushed at the instruction being compiled.
+    scopes: usize,
+}
+
+impl Chunk {
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    fn constant(&mut self, value: MalValue) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// Set the target of the jump at the given position to the next
+    /// instruction.
+    fn patch_jump(&mut self, position: usize) {
+        let target = self.code.len();
+        match self.code[position] {
+            Op::Jump(ref mut to) | Op::JumpIfFalse(ref mut to) => *to = target,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Compile the given form, evaluated in tail position or not.
+    fn compile(&mut self, ast: &MalValue, tail: bool) {
+        match **ast {
+            Symbol(symbol) => {
+                self.emit(Op::Lookup(symbol));
+            }
+            LocalSymbol(symbol, depth) => {
+                self.emit(Op::LookupAt(symbol, depth));
+            }
+            List(ref seq, _) if !seq.is_empty() => self.compile_list(ast, seq, tail),
+            Vector(ref seq) => {
+                for item in seq {
+                    self.compile(item, false);
+                }
+                self.emit(Op::Vector(seq.len()));
+            }
+            Hash(ref hash) => {
+                for (key, value) in hash {
+                    let key = self.constant(key.value().clone());
+                    self.emit(Op::Const(key));
+                    self.compile(value, false);
+                }
+                self.emit(Op::Hash(hash.len()));
+            }
+            Set(ref set) => {
+                for item in set {
+                    self.compile(item.value(), false);
+                }
+                self.emit(Op::Set(set.len()));
+            }
+            _ => {
+                let value = self.constant(ast.clone());
+                self.emit(Op::Const(value));
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: Pattern) -> usize {
+        self.patterns.push(Rc::new(pattern));
+        self.patterns.len() - 1
+    }
+
+    /// Compile the given let* (or loop) bindings, bound to their parsed keys
+    /// in a new scope (naming the functions bound to symbols, if asked to).
+    fn compile_bindings(&mut self, bindings: &MalList, keys: Vec<Pattern>, name: bool) {
+        self.emit(Op::PushScope);
+        self.scopes += 1;
+        for (key, value) in keys.into_iter().zip(bindings.iter().skip(1).step_by(2)) {
+            self.compile(value, false);
+            let op = match key {
+                Pattern::Symbol(key) => {
+                    if name {
+                        self.emit(Op::Name(key));
+                    }
+                    Op::Bind(key)
+                }
+                pattern => Op::Destructure(self.pattern(pattern)),
+            };
+            self.emit(op);
+        }
+    }
+
+    /// Compile the given form with 'eval'.
+    fn compile_eval(&mut self, ast: &MalValue) {
+        let form = self.constant(ast.clone());
+        self.emit(Op::Eval(form));
+    }
+
+    fn compile_list(&mut self, ast: &MalValue, seq: &MalList, tail: bool) {
+        let head = match *seq[0] {
+            Symbol(symbol) => Some(symbol),
+            _ => None,
+        };
+        match head {
+            Some(symbol::DO) => {
+                if seq.len() < 2 {
+                    let nil = self.constant(new_nil());
+                    self.emit(Op::Const(nil));
+                    return;
+                }
+                for item in seq.iter().skip(1).take(seq.len() - 2) {
+                    self.compile(item, false);
+                    self.emit(Op::Pop);
+                }
+                self.compile(seq.last().unwrap(), tail);
+            }
+            Some(symbol::IF) if seq.len() == 3 || seq.len() == 4 => {
+                self.compile(&seq[1], false);
+                let otherwise = self.emit(Op::JumpIfFalse(0));
+                self.compile(&seq[2], tail);
+                let end = self.emit(Op::Jump(0));
+                self.patch_jump(otherwise);
+                match seq.get(3) {
+                    Some(value) => self.compile(value, tail),
+                    None => {
+                        let nil = self.constant(new_nil());
+                        self.emit(Op::Const(nil));
+                    }
+                }
+                self.patch_jump(end);
+            }
+            Some(symbol::DEF) => match (seq.len(), seq.get(1).map(|key| &**key)) {
+                (3, Some(&Symbol(key))) => {
+                    self.compile(&seq[2], false);
+                    self.emit(Op::Def(key, None));
+                }
+                (4, Some(&Symbol(key))) if matches!(*seq[2], Str(_)) => {
+                    self.compile(&seq[3], false);
+                    let doc = self.constant(seq[2].clone());
+                    self.emit(Op::Def(key, Some(doc)));
+                }
+                _ => self.compile_eval(ast),
+            },
+            Some(symbol::LET) => {
+                let bindings = match (seq.len(), seq.get(1).map(|bindings| &**bindings)) {
+                    (3, Some(List(ref bindings, _))) | (3, Some(Vector(ref bindings)))
+                        if bindings.len() % 2 == 0 =>
+                    {
+                        bindings
+                    }
+                    _ => return self.compile_eval(ast),
+                };
+                let keys = match bindings.iter().step_by(2).map(destructure::parse).collect() {
+                    Ok(keys) => keys,
+                    Err(_) => return self.compile_eval(ast),
+                };
+                self.compile_bindings(bindings, keys, true);
+                self.compile(&seq[2], tail);
+                self.emit(Op::PopScope);
+                self.scopes -= 1;
+            }
+            Some(symbol::LOOP) => {
+                let keys = match eval::check_loop(seq) {
+                    Ok(keys) => keys,
+                    Err(_) => return self.compile_eval(ast),
+                };
+                let bindings = match *seq[1] {
+                    List(ref bindings, _) | Vector(ref bindings) => bindings,
+                    _ => unreachable!(),
+                };
+                let symbols = keys
+                    .iter()
+                    .map(|key| match *key {
+                        Pattern::Symbol(key) => Some(key),
+                        _ => None,
+                    })
+                    .collect();
+                let loop_keys = match symbols {
+                    Some(symbols) => LoopKeys::Symbols(symbols),
+                    None => {
+                        let pattern =
+                            new_vector(bindings.iter().step_by(2).cloned().collect::<Vec<_>>());
+                        let pattern = destructure::parse(&pattern).unwrap();
+                        LoopKeys::Pattern(self.pattern(pattern), keys.len())
+                    }
+                };
+                self.compile_bindings(bindings, keys, false);
+                self.loops.push(LoopTarget {
+                    keys: loop_keys,
+                    start: self.code.len(),
+                    scopes: self.scopes,
+                });
+                self.compile(&seq[2], tail);
+                self.loops.pop();
+                self.emit(Op::PopScope);
+                self.scopes -= 1;
+            }
+            // (recur values...) : bind the loop keys to the values in a new
+            // scope replacing the ones pushed since the loop was entered,
+            // and jump back to the loop body
+            Some(symbol::RECUR) if matches!(self.loops.last(), Some(target) if target.keys.len() == seq.len() - 1) =>
+            {
+                for value in seq.iter().skip(1) {
+                    self.compile(value, false);
+                }
+                let target = self.loops.last().unwrap();
+                let start = target.start;
+                let keys = match target.keys {
+                    LoopKeys::Symbols(ref keys) => Ok(keys.clone()),
+                    LoopKeys::Pattern(pattern, count) => Err((pattern, count)),
+                };
+                for _ in target.scopes - 1..self.scopes {
+                    self.emit(Op::PopScope);
+                }
+                self.emit(Op::PushScope);
+                match keys {
+                    // the values are popped from the last one, a key bound
+                    // several times being bound to its last value
+                    Ok(keys) => {
+                        for (i, &key) in keys.iter().enumerate().rev() {
+                            self.emit(if keys[i + 1..].contains(&key) {
+                                Op::Pop
+                            } else {
+                                Op::Bind(key)
+                            });
+                        }
+                    }
+                    Err((pattern, count)) => {
+                        self.emit(Op::Vector(count));
+                        self.emit(Op::Destructure(pattern));
+                    }
+                }
+                self.emit(Op::Jump(start));
+            }
+            Some(symbol::FN) => match eval::check_fn(seq) {
+                Ok(clauses) => {
+                    self.protos.push(FnProto {
+                        clauses,
+                        source: types::source_of(ast),
+                    });
+                    self.emit(Op::Closure(self.protos.len() - 1));
+                }
+                Err(_) => self.compile_eval(ast),
+            },
+            Some(symbol::DEFN) => match eval::expand_defn(ast, seq) {
+                Ok(definition) => self.compile(&definition, tail),
+                Err(_) => self.compile_eval(ast),
+            },
+            Some(symbol::LAZY_SEQ) => {
+                let mut body = seq.skip(1);
+                body.push_front(Rc::new(Symbol(symbol::DO)));
+                self.chunks.push(compile(&new_list(body)));
+                self.emit(Op::LazySeq(self.chunks.len() - 1));
+            }
+            Some(symbol::IF)
+            | Some(symbol::UNDEF)
+            | Some(symbol::RESOLVE)
+            | Some(symbol::BOUND)
+            | Some(symbol::NS_PUBLICS)
+            | Some(symbol::NS)
+            | Some(symbol::REQUIRE)
+            | Some(symbol::RECUR) => self.compile_eval(ast),
+            _ => {
+                for item in seq {
+                    self.compile(item, false);
+                }
+                self.emit(if tail {
+                    Op::TailCall(seq.len() - 1)
+                } else {
+                    Op::Call(seq.len() - 1)
+                });
+            }
+        }
+    }
+}
+
+/// Compile the given form, as the body of a function.
+pub fn compile(ast: &MalValue) -> Rc<Chunk> {
+    let mut chunk = Chunk::default();
+    chunk.compile(ast, true);
+    chunk.emit(Op::Return);
+    Rc::new(chunk)
+}
+
+/// Compiled forms, by address (see 'COMPILED').
+type CompiledForms = HashMap<*const MalType, (Weak<MalType>, Rc<Chunk>)>;
+
+thread_local! {
+    /// The compiled forms (and functions bodies) still alive, by address.
+    ///
+    /// The forms are weakly held, which is enough to prevent their address
+    /// from being reused.
+    static COMPILED: RefCell<CompiledForms> = RefCell::new(HashMap::new());
+    /// The number of compiled forms above which the dead ones are dropped.
+    static COMPILED_LIMIT: Cell<usize> = const { Cell::new(64) };
+}
+
+/// Return the compiled given form, compiling it only the first time.
+fn compiled(ast: &MalValue) -> Rc<Chunk> {
+    let key = Rc::as_ptr(ast);
+    if let Some(chunk) = COMPILED.with(|compiled| compiled.borrow().get(&key).map(|c| c.1.clone()))
+    {
+        return chunk;
+    }
+    let chunk = compile(ast);
+    COMPILED.with(|compiled| {
+        let mut compiled = compiled.borrow_mut();
+        if compiled.len() >= COMPILED_LIMIT.with(Cell::get) {
+            compiled.retain(|_, (form, _)| form.strong_count() > 0);
+            COMPILED_LIMIT.with(|limit| limit.set((2 * compiled.len()).max(64)));
+        }
+        compiled.insert(key, (Rc::downgrade(ast), chunk.clone()));
+    });
+    chunk
+}
+
+/// Evaluate the given AST in the given environment, by compiling it then
+/// running it on the virtual machine.
+pub fn eval(ast: MalValue, env: Env) -> MalResult {
+    run(compiled(&ast), env)
+}
+
+/// A function call being run.
+struct Frame {
+    chunk: Rc<Chunk>,
+    /// The position of the next instruction.
+    ip: usize,
+    env: Env,
+    /// The height of the stack when the frame was entered.
+    base: usize,
+    /// The name of the MAL function being run, if any.
+    name: Option<String>,
+}
+
+/// Run the given chunk in the given environment and return its value.
+///
+/// An error records the MAL functions it went through, as 'eval' does.
+fn run(chunk: Rc<Chunk>, env: Env) -> MalResult {
+    let _depth = limits::enter()?;
+    let mut stack = vec![];
+    let mut frames = vec![Frame {
+        chunk,
+        ip: 0,
+        env,
+        base: 0,
+        name: None,
+    }];
+    execute(&mut stack, &mut frames).map_err(|why| {
+        frames
+            .iter()
+            .rev()
+            .filter_map(|frame| frame.name.clone())
+            .fold(why, MalError::with_frame)
+    })
+}
+
+fn unbound(symbol: SymbolId) -> MalError {
+    MalError::new(
+        MalErrorKind::UnboundSymbol,
+        format!("env: '{}' not found", symbol),
+    )
+}
+
+fn execute(stack: &mut Vec<MalValue>, frames: &mut Vec<Frame>) -> MalResult {
+    loop {
+        limits::step()?;
+        let frame = frames.last_mut().unwrap();
+        let op = frame.chunk.code[frame.ip];
+        frame.ip += 1;
+        match op {
+            Op::Const(index) => stack.push(frame.chunk.constants[index].clone()),
+            Op::Lookup(symbol) => match env::lookup(&frame.env, symbol)
+                .or_else(|| namespace::lookup_qualified(&frame.env, symbol))
+            {
+                Some(value) => stack.push(value),
+                None => return Err(unbound(symbol)),
+            },
+            Op::LookupAt(symbol, depth) => match env::lookup_at(&frame.env, symbol, depth) {
+                Some(value) => stack.push(value),
+                None => return Err(unbound(symbol)),
+            },
+            Op::Eval(index) => {
+                let form = frame.chunk.constants[index].clone();
+                stack.push(eval::eval(form, frame.env.clone())?);
+            }
+            Op::Pop => {
+                stack.pop();
+            }
+            Op::Def(symbol, doc) => {
+                let doc = doc.map(|index| frame.chunk.constants[index].pr_str(false));
+                let value = name_function(stack.pop().unwrap(), &symbol.name(), doc);
+                env::define(&frame.env, symbol, value.clone());
+                stack.push(value);
+            }
+            Op::PushScope => frame.env = frame.env.new_inner(),
+            Op::PopScope => frame.env = frame.env.outer().unwrap(),
+            Op::Bind(symbol) => env::define(&frame.env, symbol, stack.pop().unwrap()),
+            Op::Name(symbol) => {
+                let value = name_function(stack.pop().unwrap(), &symbol.name(), None);
+                stack.push(value);
+            }
+            Op::Destructure(index) => {
+                let value = stack.pop().unwrap();
+                frame.chunk.patterns[index].bind_env(&frame.env, value, self::eval)?;
+            }
+            Op::Jump(target) => frame.ip = target,
+            Op::JumpIfFalse(target) => {
+                if let False | Nil = *stack.pop().unwrap() {
+                    frame.ip = target;
+                }
+            }
+            Op::Closure(index) => {
+                let proto = &frame.chunk.protos[index];
+                stack.push(new_mal_function_binding(
+                    self::eval,
+                    frame.env.clone(),
+                    proto.clauses.clone(),
+                    proto.source.clone(),
+                    Binding::Named,
+                ));
+            }
+            Op::LazySeq(index) => {
+                let (chunk, env) = (frame.chunk.chunks[index].clone(), frame.env.clone());
+                stack.push(new_lazy_seq(Box::new(move || run(chunk, env))));
+            }
+            Op::Vector(count) => {
+                let items = stack.split_off(stack.len() - count);
+                stack.push(new_vector(items));
+            }
+            Op::Hash(count) => {
+                let items = stack.split_off(stack.len() - 2 * count);
+                let mut hash = MalHashContainer::new();
+                let mut it = items.into_iter();
+                while let (Some(key), Some(value)) = (it.next(), it.next()) {
+                    hash.insert(MalHashKey::new(key), value);
+                }
+                stack.push(new_hash(hash));
+            }
+            Op::Set(count) => {
+                let items = stack.split_off(stack.len() - count);
+                stack.push(new_set(
+                    items
+                        .into_iter()
+                        .map(MalHashKey::new)
+                        .collect::<MalSetContainer>(),
+                ));
+            }
+            Op::Call(count) | Op::TailCall(count) => {
+                let args = stack.split_off(stack.len() - count);
+                let f = stack.pop().unwrap();
+                match *f {
+                    MalFunction(ref data) if data.binds_by_name() => {
+                        let (body, env) = data.bind(args)?;
+                        let callee = Frame {
+                            chunk: compiled(&body),
+                            ip: 0,
+                            env,
+                            base: stack.len(),
+                            name: Some(data.frame_name().to_string()),
+                        };
+                        if let Op::TailCall(_) = op {
+                            stack.truncate(frame.base);
+                            *frame = Frame {
+                                base: frame.base,
+                                ..callee
+                            };
+                        } else {
+                            limits::check_depth(frames.len())?;
+                            frames.push(callee);
+                        }
+                    }
+                    _ => stack.push(f.apply(args)?),
+                }
+            }
+            Op::Return => {
+                let value = stack.pop().unwrap();
+                let frame = frames.pop().unwrap();
+                stack.truncate(frame.base);
+                if frames.is_empty() {
+                    return Ok(value);
+                }
+                stack.push(value);
+            }
+        }
+    }
+}
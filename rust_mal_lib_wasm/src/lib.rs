@@ -1,5 +1,8 @@
 mod utils;
 
+use std::time::Duration;
+
+use rust_mal_lib::limits;
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -8,6 +11,17 @@ use wasm_bindgen::prelude::*;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date)]
+    fn now() -> f64;
+}
+
+/// The clock of the evaluation time limit, from the JavaScript host.
+fn host_clock() -> Duration {
+    Duration::from_secs_f64(now() / 1000.0)
+}
+
 #[wasm_bindgen]
 pub fn greet() -> String {
     "Hello WebAssembly!".into()
@@ -16,6 +30,7 @@ pub fn greet() -> String {
 #[wasm_bindgen(start)]
 pub fn main() -> Result<(), JsValue> {
     utils::set_panic_hook();
+    limits::set_clock(host_clock);
 
     Ok(())
 }
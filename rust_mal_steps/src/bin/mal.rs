@@ -0,0 +1,650 @@
+use rust_mal_lib::limits::{self, Limits};
+use rust_mal_lib::types::{MalError, MalResult, MalValue};
+use rust_mal_lib::{analyzer, env::Env, eval, namespace, reader, vm};
+use rust_mal_steps::scaffold::*;
+
+fn read(string: &str) -> MalResult {
+    reader::read_str(string)
+}
+
+fn print(expr: MalValue) -> String {
+    expr.pr_str(true)
+}
+
+/// The full interpreter, built on the shared evaluator.
+struct Mal;
+impl InterpreterScaffold<Env> for Mal {
+    const STEP_NAME: &'static str = "mal";
+
+    fn create_env() -> Result<Env, MalError> {
+        let repl_env = namespace::new_interpreter();
+        let core_env = namespace::find(&repl_env, namespace::CORE).unwrap();
+        eval::eval(read("(defn not [x] (if x false true))")?, core_env)?;
+        Ok(repl_env)
+    }
+
+    /// Read-Eval-Print the given input in the current namespace.
+    fn rep(input: &str, env: &Env) -> Result<String, MalError> {
+        limits::start();
+        let ast = read(input)?;
+        let expr = eval::eval(ast, namespace::current(env))?;
+        Ok(print(expr))
+    }
+}
+
+/// The full interpreter, evaluating on the bytecode virtual machine.
+struct MalVm;
+impl InterpreterScaffold<Env> for MalVm {
+    const STEP_NAME: &'static str = "mal-vm";
+
+    fn create_env() -> Result<Env, MalError> {
+        Mal::create_env()
+    }
+
+    /// Read-Eval-Print the given input in the current namespace.
+    fn rep(input: &str, env: &Env) -> Result<String, MalError> {
+        limits::start();
+        let ast = read(input)?;
+        let expr = vm::eval(ast, namespace::current(env))?;
+        Ok(print(expr))
+    }
+}
+
+/// The full interpreter, evaluating the analyzed forms.
+struct MalAnalyzer;
+impl InterpreterScaffold<Env> for MalAnalyzer {
+    const STEP_NAME: &'static str = "mal-analyzer";
+
+    fn create_env() -> Result<Env, MalError> {
+        Mal::create_env()
+    }
+
+    /// Read-Eval-Print the given input in the current namespace.
+    fn rep(input: &str, env: &Env) -> Result<String, MalError> {
+        limits::start();
+        let ast = read(input)?;
+        let expr = analyzer::eval(ast, namespace::current(env))?;
+        Ok(print(expr))
+    }
+}
+
+/// The call depth limit of the REPL.
+const REPL_DEPTH: usize = 10_000;
+/// The stack size of the REPL thread, large enough for 'REPL_DEPTH' nested
+/// evaluations (even unoptimized).
+const REPL_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Run the REPL, on the virtual machine if given the `--vm` flag or on the
+/// analyzer if given the `--analyze` one.
+fn main() -> Result<(), String> {
+    let repl = std::thread::Builder::new()
+        .stack_size(REPL_STACK_SIZE)
+        .spawn(|| {
+            limits::set_limits(Limits {
+                depth: Some(REPL_DEPTH),
+                ..Limits::default()
+            });
+            if std::env::args().any(|arg| arg == "--vm") {
+                cli_loop::<Env, MalVm>()
+            } else if std::env::args().any(|arg| arg == "--analyze") {
+                cli_loop::<Env, MalAnalyzer>()
+            } else {
+                cli_loop::<Env, Mal>()
+            }
+        })
+        .map_err(|err| format!("cannot start the REPL: {}", err))?;
+    repl.join().map_err(|_| "the REPL panicked".to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_mal_lib::core::Capability;
+    use rust_mal_lib::edn;
+    use rust_mal_lib::gc;
+    use rust_mal_lib::resolve;
+    use rust_mal_lib::types::{MalErrorKind, MalType};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// The spec files shared by all the evaluation backends.
+    const BACKENDS_SPECS: [&str; 19] = [
+        "step2_eval.mal",
+        "step3_env.mal",
+        "step4_if_fn_do.mal",
+        "collections.mal",
+        "sets.mal",
+        "edn.mal",
+        "lazy_seqs.mal",
+        "strings.mal",
+        "errors.mal",
+        "chars.mal",
+        "regexes.mal",
+        "collection_functions.mal",
+        "introspection.mal",
+        "metadata.mal",
+        "namespaces.mal",
+        "lexical_addressing.mal",
+        "loops.mal",
+        "destructuring.mal",
+        "multi_arity.mal",
+    ];
+
+    #[test]
+    fn test_mal_vm_specs() {
+        for spec in &BACKENDS_SPECS {
+            assert_eq!(
+                validate_against_spec::<Env, MalVm>(spec),
+                Ok(()),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_mal_vm_error_trace() {
+        let env = MalVm::create_env().unwrap();
+        MalVm::rep("(def! inner (fn* (x) (+ x \"a\")))", &env).unwrap();
+        MalVm::rep("(def! outer (fn* (x) (do (inner x) x)))", &env).unwrap();
+        let error = MalVm::rep("(count (map (fn* (x) (outer x)) [1]))", &env).unwrap_err();
+        assert_eq!(error.frames, vec!["inner", "outer", "fn*"]);
+        let error = MalVm::rep("(outer 1)", &env).unwrap_err();
+        assert_eq!(error.frames, vec!["inner", "outer"]);
+    }
+
+    #[test]
+    fn test_mal_analyzer_specs() {
+        for spec in &BACKENDS_SPECS {
+            assert_eq!(
+                validate_against_spec::<Env, MalAnalyzer>(spec),
+                Ok(()),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_mal_analyzer_error_trace() {
+        let env = MalAnalyzer::create_env().unwrap();
+        MalAnalyzer::rep("(def! inner (fn* (x) (+ x \"a\")))", &env).unwrap();
+        MalAnalyzer::rep("(def! outer (fn* (x) (do (inner x) x)))", &env).unwrap();
+        let error = MalAnalyzer::rep("(count (map (fn* (x) (outer x)) [1]))", &env).unwrap_err();
+        assert_eq!(error.frames, vec!["inner", "outer", "fn*"]);
+        let error = MalAnalyzer::rep("(outer 1)", &env).unwrap_err();
+        assert_eq!(error.frames, vec!["inner", "outer"]);
+    }
+
+    #[test]
+    fn test_mal_analyzer_definition_errors() {
+        let env = MalAnalyzer::create_env().unwrap();
+        for (definition, message) in &[
+            (
+                "(def! f (fn* (x) (let* (y) y)))",
+                "missing key or value in the let* binding list",
+            ),
+            (
+                "(def! f (fn* (x) (if x)))",
+                "wrong arity for if, should be 3 or 4",
+            ),
+            (
+                "(def! f (fn* (x) (fn* (y &) y)))",
+                "missing a pattern after '&' in the binding pattern (y &)",
+            ),
+            (
+                "(def! f (fn* (x) (let* ([a] x 1 2) a)))",
+                "invalid binding pattern 1",
+            ),
+        ] {
+            let error = MalAnalyzer::rep(definition, &env).unwrap_err();
+            assert_eq!(&error.message, message, "{}", definition);
+            assert_eq!(MalAnalyzer::rep("(bound? f)", &env).unwrap(), "false");
+            // the tree walker only fails when the body is evaluated
+            assert!(Mal::rep(definition, &env).is_ok(), "{}", definition);
+            MalAnalyzer::rep("(undef! f)", &env).unwrap();
+        }
+    }
+
+    /// Check that each evaluation limit stops the given backend with a limit
+    /// error, leaving the environment usable.
+    fn check_limits<S: InterpreterScaffold<Env>>() {
+        let env = S::create_env().unwrap();
+        let limit_error = |input: &str| {
+            let error = S::rep(input, &env).unwrap_err();
+            assert_eq!(error.kind, MalErrorKind::Limit, "{}", input);
+            error.message
+        };
+
+        limits::set_limits(Limits {
+            steps: Some(10_000),
+            ..Limits::default()
+        });
+        for input in &["(loop [i 0] (recur (+ i 1)))", "(count (range))"] {
+            assert_eq!(
+                limit_error(input),
+                "evaluation steps limit (10000) exceeded"
+            );
+        }
+        // the budget is the one of each top-level evaluation
+        for _ in 0..3 {
+            assert_eq!(
+                S::rep("(loop [i 0] (if (< i 100) (recur (+ i 1)) i))", &env).unwrap(),
+                "100"
+            );
+        }
+
+        limits::set_limits(Limits {
+            depth: Some(50),
+            ..Limits::default()
+        });
+        S::rep("(def! f (fn* (n) (if (= n 0) 0 (+ 1 (f (- n 1))))))", &env).unwrap();
+        assert_eq!(S::rep("(f 20)", &env).unwrap(), "20");
+        assert_eq!(limit_error("(f 100)"), "call depth limit (50) exceeded");
+        assert_eq!(S::rep("(f 20)", &env).unwrap(), "20");
+
+        limits::set_limits(Limits {
+            collection_size: Some(100),
+            ..Limits::default()
+        });
+        assert_eq!(S::rep("(count (range 100))", &env).unwrap(), "100");
+        let long_string = "x".repeat(101);
+        for input in &[
+            "(count (range 1000))".to_string(),
+            "(into [] (range))".to_string(),
+            "(cons 0 (into [] (range 100)))".to_string(),
+            "(str (range 60))".to_string(),
+            "(join \",\" (range 60))".to_string(),
+            "(format \"%s\" (range 60))".to_string(),
+            format!("(string->list \"{}\")", long_string),
+            format!("(re-seq (re-pattern \"x\") \"{}\")", long_string),
+            "(first (partition 101 (range)))".to_string(),
+            "(frequencies (range 1000))".to_string(),
+            "(group-by (fn* (x) x) (range 1000))".to_string(),
+            "(zipmap (range) (range))".to_string(),
+            "(union (set (into [] (range 60))) (set (into [] (range 60 120))))".to_string(),
+        ] {
+            assert_eq!(limit_error(input), "collection size limit (100) exceeded");
+        }
+
+        limits::set_limits(Limits {
+            time: Some(Duration::from_millis(50)),
+            ..Limits::default()
+        });
+        assert_eq!(
+            limit_error("(loop [i 0] (recur (+ i 1)))"),
+            "evaluation time limit (50ms) exceeded"
+        );
+
+        limits::set_limits(Limits::default());
+        let interrupted = limits::interrupt_flag();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            interrupted.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(
+            limit_error("(loop [i 0] (recur (+ i 1)))"),
+            "evaluation interrupted"
+        );
+        interrupter.join().unwrap();
+        assert_eq!(S::rep("(f 3)", &env).unwrap(), "3");
+    }
+
+    #[test]
+    fn test_mal_limits() {
+        check_limits::<Mal>();
+    }
+
+    #[test]
+    fn test_mal_vm_limits() {
+        check_limits::<MalVm>();
+    }
+
+    #[test]
+    fn test_mal_analyzer_limits() {
+        check_limits::<MalAnalyzer>();
+    }
+
+    #[test]
+    fn test_mal_bounded_memory() {
+        let backends: [fn(MalValue, Env) -> MalResult; 3] = [eval::eval, vm::eval, analyzer::eval];
+        for eval in &backends {
+            let env = namespace::current(&Mal::create_env().unwrap());
+            // each g closes over the let* environment it is bound in, and f
+            // over the namespace one
+            let f = read("(def! f (fn* (n) (if (= n 0) 0 (f (- n 1)))))").unwrap();
+            let g = read("(def! g (let* (h (fn* (n) (if (= n 0) 0 (h (- n 1))))) h))").unwrap();
+            // (the interpreter of the previous backend included)
+            gc::collect();
+            let collected = gc::stats().collected;
+            for _ in 0..10_000 {
+                eval(f.clone(), env.clone()).unwrap();
+                eval(g.clone(), env.clone()).unwrap();
+                assert!(gc::stats().envs < 4096, "{:?}", gc::stats());
+            }
+            let stats = eval(read("(gc)").unwrap(), env.clone()).unwrap();
+            assert!(gc::stats().envs < 64, "{}", print(stats));
+            // all the let* environments but the one of the last g
+            assert_eq!(gc::stats().collected - collected, 9_999);
+            let result = eval(read("(+ (f 10) (g 10))").unwrap(), env.clone());
+            assert_eq!(print(result.unwrap()), "0");
+        }
+    }
+
+    #[test]
+    fn test_mal_dropped_interpreters() {
+        let backends: [fn(MalValue, Env) -> MalResult; 3] = [eval::eval, vm::eval, analyzer::eval];
+        let forms = ["(ns other)", "(def! f (fn* (n) n))", "(ns user)"]
+            .iter()
+            .map(|input| read(input).unwrap())
+            .collect::<Vec<_>>();
+        for eval in &backends {
+            let (live, collected) = (gc::stats().envs, gc::stats().collected);
+            for _ in 0..200 {
+                let env = namespace::new_sandbox(&[Capability::Pure]);
+                for form in &forms {
+                    eval(form.clone(), namespace::current(&env)).unwrap();
+                }
+            }
+            gc::collect();
+            assert_eq!(gc::stats().envs, live, "{:?}", gc::stats());
+            // the mal.core, user and other namespaces of each interpreter
+            assert_eq!(gc::stats().collected - collected, 600);
+        }
+    }
+
+    #[test]
+    fn test_mal_sandbox() {
+        let backends: [fn(MalValue, Env) -> MalResult; 3] = [eval::eval, vm::eval, analyzer::eval];
+        for eval in &backends {
+            let env = namespace::new_sandbox(&[Capability::Pure]);
+            let rep = |input: &str| eval(read(input)?, namespace::current(&env)).map(print);
+            assert_eq!(rep("(count [1 2])").unwrap(), "2");
+            for input in &[
+                "(println 1)",
+                "(mal.core/prn 1)",
+                "((fn* (f) (f 1)) prn)",
+                "(do (ns other) (println 1))",
+                "(slurp \"tests/lib/geometry.mal\")",
+                "(gc)",
+                "(memory-stats)",
+            ] {
+                let error = rep(input).unwrap_err();
+                assert_eq!(error.kind, MalErrorKind::UnboundSymbol, "{}", input);
+            }
+            assert_eq!(rep("(resolve println)").unwrap(), "nil");
+            assert_eq!(rep("(bound? mal.core/prn)").unwrap(), "false");
+            assert_eq!(
+                rep("(require tests.lib.geometry)").unwrap_err().message,
+                "require: cannot load namespace tests.lib.geometry without the io-read capability"
+            );
+        }
+
+        let env = namespace::new_interpreter();
+        let rep = |input: &str| eval::eval(read(input)?, namespace::current(&env)).map(print);
+        assert_eq!(rep("(bound? mal.core/prn)").unwrap(), "true");
+        assert_eq!(rep("(require tests.lib.geometry)").unwrap(), "nil");
+        assert_eq!(
+            rep("(includes? (slurp \"tests/lib/geometry.mal\") \"(ns tests.lib.geometry)\")")
+                .unwrap(),
+            "true"
+        );
+        assert_eq!(
+            rep("(slurp \"tests/lib/missing.mal\")").unwrap_err().kind,
+            MalErrorKind::Value
+        );
+    }
+
+    #[test]
+    fn test_mal_step4_spec() {
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("step4_if_fn_do.mal"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_mal_resolve_nested_once() {
+        let items = |form: &MalValue| match **form {
+            MalType::List(ref seq, _) => seq.clone(),
+            _ => panic!("not a list: {}", print(form.clone())),
+        };
+        let outer = items(&read("(fn* (a) (fn* (b) (+ a b)))").unwrap());
+        let inner = resolve::resolve_fn(&outer[1], &outer[2]);
+        assert!(!Rc::ptr_eq(&inner, &outer[2]));
+        // the body of the nested function, resolved with the outer one, is
+        // not resolved again when the closure is created
+        let inner = items(&inner);
+        let body = resolve::resolve_fn(&inner[1], &inner[2]);
+        assert!(Rc::ptr_eq(&body, &inner[2]));
+    }
+
+    #[test]
+    fn test_mal_edn_write_resolved() {
+        let form = read("(fn* (a) (+ a 1))").unwrap();
+        let body = match *form {
+            MalType::List(ref seq, _) => resolve::resolve_fn(&seq[1], &seq[2]),
+            _ => unreachable!(),
+        };
+        assert!(
+            matches!(*body, MalType::List(ref seq, _) if matches!(*seq[1], MalType::LocalSymbol(..)))
+        );
+        // the symbols resolved to local bindings are written as plain symbols
+        assert_eq!(edn::write_edn(&body).unwrap(), "(+ a 1)");
+    }
+
+    #[test]
+    fn test_mal_lexical_addressing_spec() {
+        eval::set_lexical_addressing(true);
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("lexical_addressing.mal"),
+            Ok(())
+        );
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("step4_if_fn_do.mal"),
+            Ok(())
+        );
+        assert_eq!(validate_against_spec::<Env, Mal>("lazy_seqs.mal"), Ok(()));
+        assert_eq!(validate_against_spec::<Env, Mal>("metadata.mal"), Ok(()));
+        assert_eq!(validate_against_spec::<Env, Mal>("loops.mal"), Ok(()));
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("destructuring.mal"),
+            Ok(())
+        );
+        assert_eq!(validate_against_spec::<Env, Mal>("multi_arity.mal"), Ok(()));
+        eval::set_lexical_addressing(false);
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("lexical_addressing.mal"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_mal_collections_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("collections.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_sets_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("sets.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_edn_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("edn.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_lazy_seqs_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("lazy_seqs.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_strings_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("strings.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_spec_error_patterns() {
+        let parse = rust_mal_steps::spec::parser::parse_mal_spec;
+        assert!(parse("(prn 1)\n;/1\n;=>nil\n(throw 1)\n;/user error: 1\n").is_ok());
+        assert!(parse("(println \"Type error: 1\")\n;/Type error: 1\n;=>nil\n").is_ok());
+        // a mistyped error pattern is not taken for an expected standard output
+        for spec in &[
+            "(+ 1 nil)\n;/Type error: .*\n(+ 1 1)\n;=>2\n",
+            "(+ 1 nil)\n;/unbound-symbol error: .*\n",
+            ";/Syntax error: .*\n",
+        ] {
+            assert!(
+                parse(spec)
+                    .unwrap_err()
+                    .starts_with("unrecognized error pattern"),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_mal_errors_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("errors.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_error_trace() {
+        let env = Mal::create_env().unwrap();
+        let input = "(+ 1 (1 2";
+        let error = Mal::rep(input, &env).unwrap_err();
+        assert_eq!(
+            render_error(input, &error),
+            "error: reader error: expected ')', got EOF\n  | (+ 1 (1 2\n  |          ^\n"
+        );
+
+        // the span of an error in a required file is rendered against the file
+        let input = "(require tests.lib.unterminated)";
+        let error = Mal::rep(input, &env).unwrap_err();
+        assert_eq!(
+            render_error(input, &error),
+            concat!(
+                "error: reader error: expected ')', got EOF\n",
+                "  --> ./tests/lib/unterminated.mal\n",
+                "  | (def! broken (+ 1\n",
+                "  |                  ^\n"
+            )
+        );
+        let mut error = error;
+        error.file = None;
+        assert_eq!(
+            render_error(input, &error),
+            "error: reader error: expected ')', got EOF\n"
+        );
+
+        Mal::rep("(def! inner (fn* (x) (+ x \"a\")))", &env).unwrap();
+        Mal::rep("(def! outer (fn* (x) (do (inner x) x)))", &env).unwrap();
+        let input = "(count (map (fn* (x) (outer x)) [1]))";
+        let error = Mal::rep(input, &env).unwrap_err();
+        assert_eq!(error.frames, vec!["inner", "outer", "fn*"]);
+        assert_eq!(
+            render_error(input, &error),
+            concat!(
+                "error: type error: right argument must be an integer\n",
+                "  in inner\n",
+                "  in outer\n",
+                "  in fn*\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_mal_introspection_spec() {
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("introspection.mal"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_mal_repl_commands() {
+        let env = Mal::create_env().unwrap();
+        Mal::rep("(def! answer 42)", &env).unwrap();
+        let listing = repl_command(":env", &env).unwrap();
+        assert!(listing.lines().any(|line| line == "answer"));
+        assert!(listing.lines().any(|line| line == "+"));
+        assert_eq!(
+            repl_command(":doc answer", &env),
+            Some("answer\n  42\n".to_string())
+        );
+        assert_eq!(
+            repl_command(":doc undefined-x", &env),
+            Some("undefined-x: not bound\n".to_string())
+        );
+        assert_eq!(repl_command(":doc", &env), None);
+        assert_eq!(repl_command("(+ 1 2)", &env), None);
+    }
+
+    #[test]
+    fn test_mal_metadata_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("metadata.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_repl_doc_command() {
+        let env = Mal::create_env().unwrap();
+        Mal::rep("(def! inc1 \"Adds one\" (fn* (x) (+ x 1)))", &env).unwrap();
+        Mal::rep("(def! dec1 (fn* (x) (- x 1)))", &env).unwrap();
+        assert_eq!(
+            repl_command(":doc inc1", &env),
+            Some("inc1\n  (x)\n  Adds one\n".to_string())
+        );
+        assert_eq!(
+            repl_command(":doc dec1", &env),
+            Some("dec1\n  (x)\n".to_string())
+        );
+        Mal::rep("(defn pick ([a] a) ([a b] b))", &env).unwrap();
+        assert_eq!(
+            repl_command(":doc pick", &env),
+            Some("pick\n  [a]\n  [a b]\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mal_namespaces_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("namespaces.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_chars_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("chars.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_regexes_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("regexes.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_collection_functions_spec() {
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("collection_functions.mal"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_mal_loops_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("loops.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_multi_arity_spec() {
+        assert_eq!(validate_against_spec::<Env, Mal>("multi_arity.mal"), Ok(()));
+    }
+
+    #[test]
+    fn test_mal_destructuring_spec() {
+        assert_eq!(
+            validate_against_spec::<Env, Mal>("destructuring.mal"),
+            Ok(())
+        );
+    }
+}
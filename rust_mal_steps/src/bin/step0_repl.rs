@@ -25,7 +25,7 @@ impl InterpreterScaffold<Env> for Step0Repl {
 
     fn rep(input: &str, _: &Env) -> Result<String, MalError> {
         if input.is_empty() {
-            Err(MalError::ErrEmptyLine)
+            Err(MalError::empty_line())
         } else {
             Ok(print(eval(read(input.into()))))
         }
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 
-use rust_mal_lib::types::{MalError, MalResult, MalValue};
+use rust_mal_lib::symbol::SymbolId;
+use rust_mal_lib::types::{MalError, MalErrorKind, MalResult, MalValue};
 use rust_mal_lib::{env::Environment, reader, types};
 
 use rust_mal_steps::scaffold::*;
 
 #[derive(Clone)]
 struct FlatEnv {
-    data: HashMap<String, MalValue>,
+    data: HashMap<SymbolId, MalValue>,
 }
 
 impl Environment for FlatEnv {
@@ -27,23 +29,47 @@ impl Environment for FlatEnv {
 
     fn get_env_value(&self, key: &MalValue) -> MalResult {
         match **key {
-            types::MalType::Symbol(ref symbol) => match self.data.get(symbol) {
+            types::MalType::Symbol(symbol) => match self.data.get(&symbol) {
                 Some(value) => Ok(value.clone()),
-                None => types::err_string(format!("env: cannot find {}", symbol)),
+                None => types::err_string(
+                    MalErrorKind::UnboundSymbol,
+                    format!("env: '{}' not found", symbol),
+                ),
             },
-            _ => types::err_str("env: cannot get with a non-symbol key"),
+            _ => types::err_str(MalErrorKind::Type, "env: cannot get with a non-symbol key"),
         }
     }
 
     fn set_env_value(&mut self, key: MalValue, val: MalValue) -> &mut Self {
         match *key {
-            types::MalType::Symbol(ref symbol) => {
-                self.data.insert(symbol.clone(), val);
+            types::MalType::Symbol(symbol) => {
+                self.data.insert(symbol, val);
             }
             _ => panic!("step2 env: env: cannot set with a non-symbol key"),
         }
         self
     }
+
+    fn remove_env_value(&mut self, key: &MalValue) -> Option<MalValue> {
+        match **key {
+            types::MalType::Symbol(symbol) => self.data.remove(&symbol),
+            _ => None,
+        }
+    }
+
+    fn outer(&self) -> Option<Self> {
+        None
+    }
+
+    fn bindings(&self) -> Vec<(MalValue, MalValue)> {
+        let mut bindings = self
+            .data
+            .iter()
+            .map(|(&symbol, value)| (Rc::new(types::MalType::Symbol(symbol)), value.clone()))
+            .collect::<Vec<_>>();
+        bindings.sort_by(|(a, _), (b, _)| types::compare_values(a, b));
+        bindings
+    }
 }
 
 fn read(string: &str) -> MalResult {
@@ -53,14 +79,14 @@ fn read(string: &str) -> MalResult {
 fn eval_ast(ast: MalValue, env: &impl Environment) -> MalResult {
     use types::MalType::*;
     match *ast {
-        Symbol(ref symbol) => env.get_env_value(&types::new_symbol(symbol.clone())),
-        List(ref seq) | Vector(ref seq) => {
+        Symbol(_) => env.get_env_value(&ast),
+        List(ref seq, _) | Vector(ref seq) => {
             let mut ast_ev = vec![];
             for value in seq {
                 ast_ev.push(eval(value.clone(), env)?);
             }
             Ok(match *ast {
-                List(_) => types::new_list(ast_ev),
+                List(..) => types::new_list(ast_ev),
                 _ => types::new_vector(ast_ev),
             })
         }
@@ -72,21 +98,21 @@ fn eval(ast: MalValue, env: &impl Environment) -> MalResult {
     use types::MalType::*;
 
     match *ast {
-        List(_) => (),
+        List(..) => (),
         _ => return eval_ast(ast, env),
     }
 
     // ast is a list : apply the first item to the other
     let list_ev = eval_ast(ast, env)?;
     let items = match *list_ev {
-        List(ref seq) => seq,
-        _ => return types::err_str("can only apply on a List"),
+        List(ref seq, _) => seq,
+        _ => return types::err_str(MalErrorKind::Type, "can only apply on a List"),
     };
     if items.is_empty() {
         return Ok(list_ev.clone());
     }
     let f = &items[0];
-    f.apply(items[1..].to_vec())
+    f.apply(items.iter().skip(1).cloned().collect())
 }
 
 fn print(expr: MalValue) -> String {
@@ -98,17 +124,20 @@ where
     F: FnOnce(i32, i32) -> i32,
 {
     if args.len() != 2 {
-        return types::err_string(format!(
-            "wrong arity ({}) for operation between 2 integers",
-            args.len()
-        ));
+        return types::err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for operation between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         types::MalType::Integer(left) => match *args[1] {
             types::MalType::Integer(right) => Ok(types::new_integer(f(left, right))),
-            _ => types::err_str("right argument must be an integer"),
+            _ => types::err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => types::err_str("left argument must be an integer"),
+        _ => types::err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 fn add(args: Vec<MalValue>) -> MalResult {
@@ -123,23 +152,26 @@ fn mul(args: Vec<MalValue>) -> MalResult {
 
 fn div(args: Vec<MalValue>) -> MalResult {
     if args.len() != 2 {
-        return types::err_string(format!(
-            "wrong arity ({}) for operation between 2 integers",
-            args.len()
-        ));
+        return types::err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for operation between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         types::MalType::Integer(left) => match *args[1] {
             types::MalType::Integer(right) => {
                 if right == 0 {
-                    types::err_str("cannot divide by 0")
+                    types::err_str(MalErrorKind::DivisionByZero, "cannot divide by 0")
                 } else {
                     Ok(types::new_integer(left / right))
                 }
             }
-            _ => types::err_str("right argument must be an integer"),
+            _ => types::err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => types::err_str("left argument must be an integer"),
+        _ => types::err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 
@@ -150,19 +182,19 @@ impl InterpreterScaffold<FlatEnv> for Step2Eval {
     fn create_env() -> Result<FlatEnv, MalError> {
         let mut env = FlatEnv::new(None);
         env.set_env_value(
-            types::new_symbol("+".into()),
+            types::new_symbol("+"),
             types::new_function(add, Some(2), "+"),
         )
         .set_env_value(
-            types::new_symbol("-".into()),
+            types::new_symbol("-"),
             types::new_function(sub, Some(2), "-"),
         )
         .set_env_value(
-            types::new_symbol("*".into()),
+            types::new_symbol("*"),
             types::new_function(mul, Some(2), "*"),
         )
         .set_env_value(
-            types::new_symbol("/".into()),
+            types::new_symbol("/"),
             types::new_function(div, Some(2), "/"),
         );
         Ok(env)
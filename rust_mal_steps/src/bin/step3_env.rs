@@ -1,9 +1,9 @@
 use rust_mal_lib::env::{Env, Environment};
-use rust_mal_lib::output;
 use rust_mal_lib::reader;
+use rust_mal_lib::symbol::{self, SymbolId};
 use rust_mal_lib::types::{
-    err_str, err_string, new_function, new_integer, new_list, new_nil, new_symbol, new_vector,
-    MalError, MalResult, MalType::*, MalValue,
+    err_str, err_string, new_function, new_integer, new_list, new_symbol, new_vector, MalError,
+    MalErrorKind, MalList, MalResult, MalType::*, MalValue,
 };
 
 use rust_mal_steps::scaffold::*;
@@ -15,13 +15,13 @@ fn read(string: &str) -> MalResult {
 fn eval_ast(ast: MalValue, env: &Env) -> MalResult {
     match *ast {
         Symbol(_) => env.get_env_value(&ast),
-        List(ref seq) | Vector(ref seq) => {
+        List(ref seq, _) | Vector(ref seq) => {
             let mut ast_ev = vec![];
             for value in seq {
                 ast_ev.push(eval(value.clone(), env.clone())?);
             }
             Ok(match *ast {
-                List(_) => new_list(ast_ev),
+                List(..) => new_list(ast_ev),
                 _ => new_vector(ast_ev),
             })
         }
@@ -31,62 +31,55 @@ fn eval_ast(ast: MalValue, env: &Env) -> MalResult {
 
 fn eval(ast: MalValue, mut env: Env) -> MalResult {
     let ast_temp = ast.clone();
-    let (arg0_symbol, args): (Option<&str>, &Vec<MalValue>) = match *ast_temp {
-        List(ref seq) => {
+    let (arg0_symbol, args): (Option<SymbolId>, &MalList) = match *ast_temp {
+        List(ref seq, _) => {
             if seq.is_empty() {
                 return Ok(ast);
             }
             match *seq[0] {
-                Symbol(ref symbol) => (Some(&symbol[..]), seq),
+                Symbol(symbol) => (Some(symbol), seq),
                 _ => (None, seq),
             }
         }
         _ => return eval_ast(ast, &env),
     };
 
-    if let Some(slice) = arg0_symbol {
-        match slice {
+    if let Some(special_form) = arg0_symbol {
+        match special_form {
             // (def! key value) ; key must be a Symbol
             // bind the evaluated value in env with the unevaluated key
-            "def!" => {
+            symbol::DEF => {
                 if args.len() != 3 {
-                    return err_str("wrong arity for \"def!\", should be 2");
+                    return err_str(MalErrorKind::Arity, "wrong arity for \"def!\", should be 2");
                 }
                 let key = args[1].clone();
-                let value = match eval(args[2].clone(), env.clone()) {
-                    Ok(result) => result,
-                    Err(why) => {
-                        // abort without failure
-                        output::warning(&format!("evaluation error for \"def!\": {:?}", why));
-                        return Ok(new_nil());
-                    }
-                };
+                let value = eval(args[2].clone(), env.clone())?;
                 match *key {
                     Symbol(_) => {
                         env.set_env_value(key, value.clone());
                         return Ok(value);
                     }
                     _ => {
-                        return err_str("def! with non-symbol as a key");
+                        return err_str(MalErrorKind::Syntax, "def! with non-symbol as a key");
                     }
                 }
             }
             // (let* (key0 value0 key1 value1 ...) value)
             // evaluate value in a temporary sub-environment where
             // the given (key: Symbol / value: _) pairs are set
-            "let*" => {
+            symbol::LET => {
                 if args.len() != 3 {
-                    return err_str("wrong arity for \"let*\", should be 2");
+                    return err_str(MalErrorKind::Arity, "wrong arity for \"let*\", should be 2");
                 }
                 let mut env_let = env.new_inner();
                 let bindings = args[1].clone();
                 match *bindings {
-                    List(ref bindings_seq) => {
+                    List(ref bindings_seq, _) | Vector(ref bindings_seq) => {
                         if bindings_seq.len() % 2 != 0 {
-                            return err_str(concat!(
-                                "missing key or value ",
-                                "in the let* binding list"
-                            ));
+                            return err_str(
+                                MalErrorKind::Syntax,
+                                concat!("missing key or value ", "in the let* binding list"),
+                            );
                         }
                         let mut it = bindings_seq.iter();
                         while it.len() >= 2 {
@@ -97,11 +90,16 @@ fn eval(ast: MalValue, mut env: Env) -> MalResult {
                                     let value = eval(expr.clone(), env_let.clone())?;
                                     env_let.set_env_value(key.clone(), value);
                                 }
-                                _ => return err_str("non-symbol key in the let* binding list"),
+                                _ => {
+                                    return err_str(
+                                        MalErrorKind::Syntax,
+                                        "non-symbol key in the let* binding list",
+                                    )
+                                }
                             }
                         }
                     }
-                    _ => return err_str("let* with non-list binding"),
+                    _ => return err_str(MalErrorKind::Syntax, "let* with non-list binding"),
                 }
                 return eval(args[2].clone(), env_let);
             }
@@ -112,14 +110,14 @@ fn eval(ast: MalValue, mut env: Env) -> MalResult {
 
     let list_ev = eval_ast(ast, &env)?;
     let items = match *list_ev {
-        List(ref seq) => seq,
-        _ => return err_str("can only apply on a list"),
+        List(ref seq, _) => seq,
+        _ => return err_str(MalErrorKind::Type, "can only apply on a list"),
     };
     if items.is_empty() {
         return Ok(list_ev.clone());
     }
     let f = &items[0];
-    f.apply(items[1..].to_vec())
+    f.apply(items.iter().skip(1).cloned().collect())
 }
 
 fn print(expr: MalValue) -> String {
@@ -131,17 +129,20 @@ where
     F: FnOnce(i32, i32) -> i32,
 {
     if args.len() != 2 {
-        return err_string(format!(
-            "wrong arity ({}) for operation between 2 integers",
-            args.len()
-        ));
+        return err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for operation between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         Integer(left) => match *args[1] {
             Integer(right) => Ok(new_integer(f(left, right))),
-            _ => err_str("right argument must be an integer"),
+            _ => err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => err_str("left argument must be an integer"),
+        _ => err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 fn add(args: Vec<MalValue>) -> MalResult {
@@ -156,23 +157,26 @@ fn mul(args: Vec<MalValue>) -> MalResult {
 
 fn div(args: Vec<MalValue>) -> MalResult {
     if args.len() != 2 {
-        return err_string(format!(
-            "wrong arity ({}) for operation between 2 integers",
-            args.len()
-        ));
+        return err_string(
+            MalErrorKind::Arity,
+            format!(
+                "wrong arity ({}) for operation between 2 integers",
+                args.len()
+            ),
+        );
     }
     match *args[0] {
         Integer(left) => match *args[1] {
             Integer(right) => {
                 if right == 0 {
-                    err_str("cannot divide by 0")
+                    err_str(MalErrorKind::DivisionByZero, "cannot divide by 0")
                 } else {
                     Ok(new_integer(left / right))
                 }
             }
-            _ => err_str("right argument must be an integer"),
+            _ => err_str(MalErrorKind::Type, "right argument must be an integer"),
         },
-        _ => err_str("left argument must be an integer"),
+        _ => err_str(MalErrorKind::Type, "left argument must be an integer"),
     }
 }
 
@@ -183,10 +187,10 @@ impl InterpreterScaffold<Env> for Step3Env {
     fn create_env() -> Result<Env, MalError> {
         let mut repl_env: Env = Environment::new(None);
         repl_env
-            .set_env_value(new_symbol("+".into()), new_function(add, Some(2), "+"))
-            .set_env_value(new_symbol("-".into()), new_function(sub, Some(2), "-"))
-            .set_env_value(new_symbol("*".into()), new_function(mul, Some(2), "*"))
-            .set_env_value(new_symbol("/".into()), new_function(div, Some(2), "/"));
+            .set_env_value(new_symbol("+"), new_function(add, Some(2), "+"))
+            .set_env_value(new_symbol("-"), new_function(sub, Some(2), "-"))
+            .set_env_value(new_symbol("*"), new_function(mul, Some(2), "*"))
+            .set_env_value(new_symbol("/"), new_function(div, Some(2), "/"));
         Ok(repl_env)
     }
 
@@ -1,11 +1,13 @@
 use rust_mal_lib::types::{
-    err_str, new_function, new_list, new_mal_function, new_nil, new_str, new_symbol, new_vector,
-    MalError, MalResult, MalType::*, MalValue,
+    err_str, new_hash, new_list, new_mal_function, new_nil, new_set, new_symbol, new_vector,
+    MalError, MalErrorKind, MalHashContainer, MalHashKey, MalList, MalResult, MalSetContainer,
+    MalType::*, MalValue,
 };
 use rust_mal_lib::{
-    core,
+    core, destructure,
     env::{Env, Environment},
-    output, reader,
+    reader,
+    symbol::{self, SymbolId},
 };
 use rust_mal_steps::scaffold::*;
 
@@ -16,48 +18,62 @@ fn read(string: &str) -> MalResult {
 fn eval_ast(ast: MalValue, env: &Env) -> MalResult {
     match *ast {
         Symbol(_) => env.get_env_value(&ast),
-        List(ref seq) | Vector(ref seq) => {
+        List(ref seq, _) | Vector(ref seq) => {
             let mut ast_ev = vec![];
             for value in seq {
                 ast_ev.push(eval(value.clone(), env.clone())?);
             }
             Ok(match *ast {
-                List(_) => new_list(ast_ev),
+                List(..) => new_list(ast_ev),
                 _ => new_vector(ast_ev),
             })
         }
+        Hash(ref hash) => {
+            let mut hash_ev = MalHashContainer::new();
+            for (key, value) in hash {
+                hash_ev.insert(key.clone(), eval(value.clone(), env.clone())?);
+            }
+            Ok(new_hash(hash_ev))
+        }
+        Set(ref set) => {
+            let mut set_ev = MalSetContainer::new();
+            for item in set {
+                set_ev.insert(MalHashKey::new(eval(item.value().clone(), env.clone())?));
+            }
+            Ok(new_set(set_ev))
+        }
         _ => Ok(ast.clone()),
     }
 }
 
 fn eval(ast: MalValue, mut env: Env) -> MalResult {
     let ast_temp = ast.clone();
-    let (arg0_symbol, args): (Option<&str>, &Vec<MalValue>) = match *ast_temp {
-        List(ref seq) => {
+    let (arg0_symbol, args): (Option<SymbolId>, &MalList) = match *ast_temp {
+        List(ref seq, _) => {
             if seq.is_empty() {
                 return Ok(ast);
             }
             match *seq[0] {
-                Symbol(ref symbol) => (Some(&symbol[..]), seq),
+                Symbol(symbol) => (Some(symbol), seq),
                 _ => (None, seq),
             }
         }
         _ => return eval_ast(ast, &env),
     };
 
-    if let Some(slice) = arg0_symbol {
-        match slice {
+    if let Some(special_form) = arg0_symbol {
+        match special_form {
             // (do items...) : evaluate all items and return the last one
-            "do" => match *eval_ast(new_list(args[1..].to_vec()), &env)? {
-                List(ref seq) => return Ok(seq[seq.len() - 1].clone()),
-                _ => return err_str("invalid do call"),
+            symbol::DO => match *eval_ast(new_list(args.skip(1)), &env)? {
+                List(ref seq, _) => return Ok(seq.last().unwrap().clone()),
+                _ => return err_str(MalErrorKind::Syntax, "invalid do call"),
             },
             // (if condition if_condition_not_nil_or_false otherwise)
             // if 'otherwise' is not provided, return nil if 'condition'
             // evaluates to nil or false
-            "if" => {
+            symbol::IF => {
                 if args.len() < 3 || args.len() > 4 {
-                    return err_str("wrong arity for if, should be 3 or 4");
+                    return err_str(MalErrorKind::Arity, "wrong arity for if, should be 3 or 4");
                 }
                 match *eval(args[1].clone(), env.clone())? {
                     False | Nil => {
@@ -72,45 +88,38 @@ fn eval(ast: MalValue, mut env: Env) -> MalResult {
             }
             // (def! key value) ; key must be a Symbol
             // bind the evaluated value in env with the unevaluated key
-            "def!" => {
+            symbol::DEF => {
                 if args.len() != 3 {
-                    return err_str("wrong arity for def!, should be 2");
+                    return err_str(MalErrorKind::Arity, "wrong arity for def!, should be 2");
                 }
                 let key = args[1].clone();
-                let value = match eval(args[2].clone(), env.clone()) {
-                    Ok(result) => result,
-                    Err(why) => {
-                        // abort without failure
-                        output::warning(&format!("evaluation error for \"def!\": {:?}", why));
-                        return Ok(new_nil());
-                    }
-                };
+                let value = eval(args[2].clone(), env.clone())?;
                 match *key {
                     Symbol(_) => {
                         env.set_env_value(key, value.clone());
                         return Ok(value);
                     }
                     _ => {
-                        return err_str("def! with non-symbol as a key");
+                        return err_str(MalErrorKind::Syntax, "def! with non-symbol as a key");
                     }
                 }
             }
             // (let* (key0 value0 key1 value1 ...) value)
             // evaluate value in a temporary sub-environment where
             // the given (key: Symbol / value: _) pairs are set
-            "let*" => {
+            symbol::LET => {
                 if args.len() != 3 {
-                    return err_str("wrong arity for \"let*\", should be 2");
+                    return err_str(MalErrorKind::Arity, "wrong arity for \"let*\", should be 2");
                 }
                 let mut env_let: Env = Environment::new(Some(&env));
                 let bindings = args[1].clone();
                 match *bindings {
-                    List(ref bindings_seq) => {
+                    List(ref bindings_seq, _) | Vector(ref bindings_seq) => {
                         if bindings_seq.len() % 2 != 0 {
-                            return err_str(concat!(
-                                "missing key or value ",
-                                "in the let* binding list"
-                            ));
+                            return err_str(
+                                MalErrorKind::Syntax,
+                                concat!("missing key or value ", "in the let* binding list"),
+                            );
                         }
                         let mut it = bindings_seq.iter();
                         while it.len() >= 2 {
@@ -121,29 +130,34 @@ fn eval(ast: MalValue, mut env: Env) -> MalResult {
                                     let value = eval(expr.clone(), env_let.clone())?;
                                     env_let.set_env_value(key.clone(), value);
                                 }
-                                _ => return err_str("non-symbol key in the let* binding list"),
+                                _ => {
+                                    let pattern = destructure::parse(key)?;
+                                    let value = eval(expr.clone(), env_let.clone())?;
+                                    pattern.bind_env(&env_let, value, eval)?;
+                                }
                             }
                         }
                     }
-                    _ => return err_str("let* with non-list binding"),
+                    _ => return err_str(MalErrorKind::Syntax, "let* with non-list binding"),
                 }
                 return eval(args[2].clone(), env_let);
             }
             // (fn* (args...) exp)
-            "fn*" => {
+            symbol::FN => {
                 if args.len() != 3 {
-                    return err_str("wrong arity for fn*, should be 2");
+                    return err_str(MalErrorKind::Arity, "wrong arity for fn*, should be 2");
                 }
                 let fn_args = args[1].clone();
                 match *fn_args {
-                    List(_) => (),
-                    _ => return err_str("fn* with non-list arguments"),
+                    List(..) | Vector(_) => (),
+                    _ => return err_str(MalErrorKind::Syntax, "fn* with non-list arguments"),
                 }
                 return Ok(new_mal_function(
                     self::eval,
                     env,
                     args[1].clone(),
                     args[2].clone(),
+                    None,
                 ));
             }
             // otherwise : apply the first item to the other
@@ -153,14 +167,14 @@ fn eval(ast: MalValue, mut env: Env) -> MalResult {
 
     let list_ev = eval_ast(ast, &env)?;
     let items = match *list_ev {
-        List(ref seq) => seq,
-        _ => return err_str("can only apply on a list"),
+        List(ref seq, _) => seq,
+        _ => return err_str(MalErrorKind::Type, "can only apply on a list"),
     };
     if items.is_empty() {
         return Ok(list_ev.clone());
     }
     let f = &items[0];
-    f.apply(items[1..].to_vec())
+    f.apply(items.iter().skip(1).cloned().collect())
 }
 
 fn print(expr: MalValue) -> String {
@@ -174,16 +188,8 @@ impl InterpreterScaffold<Env> for Step4IfFnDo {
     fn create_env() -> Result<Env, MalError> {
         let mut repl_env: Env = Environment::new(None);
         for (symbol_string, core_function_value) in core::ns() {
-            repl_env.set_env_value(new_symbol(symbol_string), core_function_value);
+            repl_env.set_env_value(new_symbol(&symbol_string), core_function_value);
         }
-        repl_env.set_env_value(
-            new_symbol("prn".into()),
-            new_function(
-                |v: Vec<MalValue>| Ok(new_str(print(v[0].clone()))),
-                Some(1),
-                "prn",
-            ),
-        );
         Self::rep("(def! not (fn* (x) (if x false true)))", &repl_env)?;
         Ok(repl_env)
     }
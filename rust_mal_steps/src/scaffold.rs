@@ -1,7 +1,10 @@
 use rustyline::{error::ReadlineError, Editor};
 
-use rust_mal_lib::env::Environment;
-use rust_mal_lib::types::MalError;
+use std::sync::atomic::Ordering;
+
+use rust_mal_lib::env::{self, Environment};
+use rust_mal_lib::limits;
+use rust_mal_lib::types::{new_symbol, MalError, MalErrorKind, MalType};
 
 use crate::spec::{checker::check_against_mal_spec, parser::load_and_parse_mal_spec};
 
@@ -18,12 +21,18 @@ pub trait InterpreterScaffold<E: Environment> {
 }
 
 /// Launch a Read-Eval-Print Loop.
+///
+/// Ctrl-C interrupts the evaluation in progress (see 'limits::interrupt_flag')
+/// and exits the loop when pressed at the prompt.
 pub fn cli_loop<E, S>() -> Result<(), String>
 where
     E: Environment,
     S: InterpreterScaffold<E>,
 {
-    let repl_env = S::create_env().map_err(|err| format!("{:?}", err))?;
+    let repl_env = S::create_env().map_err(|err| err.to_string())?;
+    let interrupted = limits::interrupt_flag();
+    ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed))
+        .map_err(|err| format!("cannot handle Ctrl-C: {}", err))?;
     let mut rl = Editor::<()>::new();
     let rl_history = format!("history-{}.txt", S::STEP_NAME);
     let _ = rl.load_history(&rl_history);
@@ -31,10 +40,15 @@ where
     loop {
         match rl.readline(&prompt) {
             Ok(input) => {
+                if let Some(output) = repl_command(&input, &repl_env) {
+                    print!("{}", output);
+                    rl.add_history_entry(&input);
+                    continue;
+                }
                 match S::rep(&input, &repl_env) {
                     Ok(result) => println!("{}", result),
-                    Err(MalError::ErrEmptyLine) => continue,
-                    Err(MalError::ErrString(why)) => println!("error: {}", why),
+                    Err(ref why) if why.kind == MalErrorKind::EmptyLine => continue,
+                    Err(why) => print!("{}", render_error(&input, &why)),
                 }
                 rl.add_history_entry(&input);
             }
@@ -56,6 +70,87 @@ where
         .map_err(|err| format!("cannot save history: {}", err))
 }
 
+/// Run the given input if it is a REPL meta-command, and return its output:
+///
+/// - `:env` lists the symbols bound in the REPL environment (and its outers);
+/// - `:doc symbol` describes the value bound to the symbol: its parameters
+///   lists and docstring for a MAL function, its printed value otherwise.
+pub fn repl_command<E: Environment>(input: &str, env: &E) -> Option<String> {
+    let mut words = input.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(":env"), None, _) => Some(
+            env::chain(env)
+                .flat_map(|env| env.bindings())
+                .map(|(symbol, _)| format!("{}\n", symbol.pr_str(true)))
+                .collect(),
+        ),
+        (Some(":doc"), Some(symbol), None) => Some(match env.get_env_value(&new_symbol(symbol)) {
+            Ok(value) => match *value {
+                MalType::MalFunction(ref data) => {
+                    let mut doc = format!("{}\n", symbol);
+                    for params in data.arglists() {
+                        doc.push_str(&format!("  {}\n", params.pr_str(true)));
+                    }
+                    if let Some(docstring) = data.doc() {
+                        doc.push_str(&format!("  {}\n", docstring));
+                    }
+                    doc
+                }
+                _ => format!("{}\n  {}\n", symbol, value.pr_str(true)),
+            },
+            Err(_) => format!("{}: not bound\n", symbol),
+        }),
+        _ => None,
+    }
+}
+
+/// Render the given error, raised for the given input, as a readable trace:
+/// the error itself, the line of the input (or of the file it occurred in)
+/// with its faulty part underlined (if known) then the MAL functions it went
+/// through, innermost first.
+pub fn render_error(input: &str, error: &MalError) -> String {
+    let mut trace = format!("error: {}\n", error);
+    let text = match error.file {
+        Some((ref path, ref text)) => {
+            trace.push_str(&format!("  --> {}\n", path));
+            &text[..]
+        }
+        None => input,
+    };
+    if let Some(mut span) = error.span {
+        // errors at the end of the text point past its trailing whitespace
+        if span.start == text.len() {
+            span.start = text.trim_end().len();
+            span.end = span.start;
+        }
+        // the line of the span, if it is within the text
+        let line = text.get(..span.start).and_then(|before| {
+            let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+            let line_end = text[span.start..]
+                .find('\n')
+                .map_or(text.len(), |newline| span.start + newline);
+            let underlined = text.get(span.start..span.end.min(line_end).max(span.start))?;
+            Some((
+                &text[line_start..line_end],
+                before[line_start..].chars().count(),
+                underlined.chars().count().max(1),
+            ))
+        });
+        if let Some((line, start, width)) = line {
+            trace.push_str(&format!(
+                "  | {}\n  | {}{}\n",
+                line,
+                " ".repeat(start),
+                "^".repeat(width)
+            ));
+        }
+    }
+    for frame in &error.frames {
+        trace.push_str(&format!("  in {}\n", frame));
+    }
+    trace
+}
+
 pub fn validate_against_spec<E, S>(filename: &str) -> Result<(), String>
 where
     E: Environment,
@@ -63,7 +158,7 @@ where
 {
     println!("########## validating {}... ##########", S::STEP_NAME);
     let lines = load_and_parse_mal_spec(filename)?;
-    let env = S::create_env().map_err(|err| format!("{:?}", err))?;
+    let env = S::create_env().map_err(|err| err.to_string())?;
     check_against_mal_spec(&lines, env, &|input, env| S::rep(input, env))
-        .map_err(|err| format!("{:?}", err))
+        .map_err(|err| err.to_string())
 }
@@ -1,6 +1,17 @@
 use super::MalTestingLine;
 
-use rust_mal_lib::{env::Environment, types::MalError};
+use regex::Regex;
+
+use rust_mal_lib::{env::Environment, reader, types::MalError};
+
+/// Return true if the output and the expected one can both be read as equal
+/// MAL values.
+fn output_equals(output: &str, expected: &str) -> bool {
+    match (reader::read_str(output), reader::read_str(expected)) {
+        (Ok(output_value), Ok(expected_value)) => output_value == expected_value,
+        _ => false,
+    }
+}
 
 pub fn check_against_mal_spec<E, REP>(
     lines: &[MalTestingLine],
@@ -12,15 +23,14 @@ where
     REP: Fn(&str, &mut E) -> Result<String, MalError>,
 {
     let mut optional = false;
-    let mut current_section = String::new();
     for line in lines {
         match line {
             MalTestingLine::ToggleOptional(value) => optional = *value,
             MalTestingLine::BeginSection(name) => {
-                current_section = name.clone();
-                println!("> starting section: {}", current_section);
+                println!("> starting section: {}", name);
             }
-            MalTestingLine::InputShouldOutput(inputs, expected) => {
+            MalTestingLine::InputShouldOutput(inputs, expected)
+            | MalTestingLine::InputShouldEqual(inputs, expected) => {
                 let mut output = String::new();
                 if optional {
                     println!("###optional###");
@@ -31,17 +41,17 @@ where
                     output = rep(input, &mut env)?;
                     println!(">{}", output);
                 }
-                let matches = output == *expected;
-                // assert!(
-                //     matches,
-                //     "\n{}\nSHOULD BE\n{}\nFOR INPUT\n{}\n",
-                //     output, expected, input
-                // );
-                if !matches {
-                    println!("\n{}\nSHOULD BE\n{}\n", output, expected);
-                }
+                let matches = match line {
+                    MalTestingLine::InputShouldEqual(..) => output_equals(&output, expected),
+                    _ => output == *expected,
+                };
+                assert!(
+                    matches,
+                    "\n{}\nSHOULD BE\n{}\nFOR INPUT\n{:?}\n",
+                    output, expected, inputs
+                );
             }
-            MalTestingLine::InputShouldThrow(inputs) => {
+            MalTestingLine::InputShouldThrow(inputs, pattern) => {
                 if optional {
                     println!("###optional###");
                 }
@@ -50,12 +60,21 @@ where
                     println!("#{}", input);
                     output = rep(input, &mut env);
                 }
-                // TODO: spec error type validation
-                let matches = output.is_err();
+                let error = match output {
+                    Ok(_) => panic!(
+                        "\n{:?}\nSHOULD BE AN ERROR FOR INPUT\n{:?}\n",
+                        output, inputs,
+                    ),
+                    Err(error) => error.to_string(),
+                };
+                let re = Regex::new(&format!("(?s)^(?:{})$", pattern))
+                    .unwrap_or_else(|why| panic!("invalid error pattern {}: {}", pattern, why));
                 assert!(
-                    matches,
-                    "\n{:?}\nSHOULD BE AN ERROR FOR INPUT\n{:?}\n",
-                    output, inputs,
+                    re.is_match(&error),
+                    "\n{}\nSHOULD MATCH\n{}\nFOR INPUT\n{:?}\n",
+                    error,
+                    pattern,
+                    inputs,
                 );
             }
         }
@@ -19,17 +19,50 @@ fn load_local_mal_spec_file(filepath: &Path) -> Result<BufReader<File>, String>
     })?))
 }
 
-fn parse_mal_spec(mut reader: BufReader<File>) -> Result<Vec<MalTestingLine>, String> {
-    // read all lines
-    let mut content = String::new();
-    reader
-        .read_to_string(&mut content)
-        .map_err(|err| format!("parse_mal_spec reading error: {}", err))?;
+/// Return true if the line is the pattern of an expected error: either a
+/// pattern matching anything first (";/.*"), or one anchored on the displayed
+/// error kind (e.g. ";/reader error: ...").
+fn is_error_pattern(line: &str) -> bool {
+    let pattern = match line.strip_prefix(";/") {
+        Some(pattern) => pattern,
+        None => return false,
+    };
+    pattern.starts_with('.')
+        || pattern.split_once(" error: ").is_some_and(|(kind, _)| {
+            !kind.is_empty() && kind.chars().all(|c| c.is_ascii_lowercase() || c == ' ')
+        })
+}
+
+/// Return true if the line looks like the pattern of an expected error
+/// (";/<kind> error: ...") without being a recognized one: unless it is the
+/// expected standard output of an input (followed by its ";=>" output), it
+/// is most likely a mistyped error pattern (e.g. ";/Type error: ...").
+fn looks_like_error_pattern(line: &str) -> bool {
+    line.strip_prefix(";/")
+        .and_then(|pattern| pattern.split_once(" error:"))
+        .is_some_and(|(kind, _)| {
+            !kind.is_empty() && !kind.contains(char::is_whitespace) && !kind.contains('"')
+        })
+}
+
+/// Return the error of a line looking like an error pattern not followed by
+/// the expected output of its input.
+fn unrecognized_error_pattern(line: &str) -> String {
+    format!(
+        "unrecognized error pattern \"{}\" (not a known error kind, nor followed by \";=>\")",
+        line
+    )
+}
+
+/// Parse the given content of a MAL spec file.
+pub fn parse_mal_spec(content: &str) -> Result<Vec<MalTestingLine>, String> {
     let lines: Vec<String> = content.split('\n').map(|l| l.trim().into()).collect();
 
     // parse
     let mut parsed_lines = Vec::with_capacity(lines.len());
     let mut declaring_input: Vec<String> = vec![];
+    // the last line of the input being declared looking like an error pattern
+    let mut error_like_line: Option<String> = None;
     for line in lines {
         if line.is_empty() {
             continue;
@@ -62,26 +95,65 @@ fn parse_mal_spec(mut reader: BufReader<File>) -> Result<Vec<MalTestingLine>, St
                 output.into(),
             ));
             declaring_input = vec![];
+            error_like_line = None;
             continue;
         }
 
-        let error = line.trim_start_matches(";/.");
-        if error.len() != line.len() {
+        let output = line.trim_start_matches(";=~");
+        if output.len() != line.len() {
             if declaring_input.is_empty() {
-                return Err(format!("no matching input for error output \"{}\"", output));
+                return Err(format!("no matching input for output \"{}\"", output));
             }
-            parsed_lines.push(MalTestingLine::InputShouldThrow(declaring_input.clone()));
+            parsed_lines.push(MalTestingLine::InputShouldEqual(
+                declaring_input.clone(),
+                output.into(),
+            ));
             declaring_input = vec![];
+            error_like_line = None;
             continue;
         }
 
+        if is_error_pattern(&line) {
+            if declaring_input.is_empty() {
+                return Err(format!("no matching input for error output \"{}\"", line));
+            }
+            parsed_lines.push(MalTestingLine::InputShouldThrow(
+                declaring_input.clone(),
+                line[2..].into(),
+            ));
+            declaring_input = vec![];
+            error_like_line = None;
+            continue;
+        }
+
+        // expected standard output (e.g. of "prn"), not captured by the checker
+        if line.starts_with(";/") {
+            if looks_like_error_pattern(&line) {
+                if declaring_input.is_empty() {
+                    return Err(unrecognized_error_pattern(&line));
+                }
+                error_like_line = Some(line);
+            }
+            continue;
+        }
+
+        if let Some(error_like_line) = error_like_line {
+            return Err(unrecognized_error_pattern(&error_like_line));
+        }
         declaring_input.push(line);
     }
-    Ok(parsed_lines)
+    match error_like_line {
+        Some(error_like_line) => Err(unrecognized_error_pattern(&error_like_line)),
+        None => Ok(parsed_lines),
+    }
 }
 
 pub fn load_and_parse_mal_spec(filename: &str) -> Result<Vec<MalTestingLine>, String> {
     let filename = format!("./tests/{}", filename);
     let filepath = Path::new(&filename);
-    parse_mal_spec(load_local_mal_spec_file(&filepath)?)
+    let mut content = String::new();
+    load_local_mal_spec_file(filepath)?
+        .read_to_string(&mut content)
+        .map_err(|err| format!("parse_mal_spec reading error: {}", err))?;
+    parse_mal_spec(&content)
 }